@@ -0,0 +1,63 @@
+//! Neighbor lookups on a large maze: the map stored as nested rows versus a
+//! flat `Grid`, and probing every direction through `get` versus
+//! `Grid::neighbors4`.
+
+use aoc_utils::genlib::{maze, Rng};
+use aoc_utils::geom::{Direction, Point};
+use aoc_utils::grid::Grid;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::convert::TryFrom;
+
+const SIZE: usize = 1001;
+
+fn nested_get(rows: &[Vec<bool>], (x, y): Point) -> Option<bool> {
+    let x = usize::try_from(x).ok()?;
+    let y = usize::try_from(y).ok()?;
+    rows.get(y)?.get(x).cloned()
+}
+
+/// For each open cell, how many of its four neighbors are open too.
+fn open_neighbors(
+    points: impl Iterator<Item = Point>,
+    open: impl Fn(Point) -> Option<bool>,
+) -> usize {
+    points
+        .filter(|&p| open(p) == Some(true))
+        .map(|p| {
+            Direction::ALL
+                .iter()
+                .filter(|d| open(d.step(p)) == Some(true))
+                .count()
+        })
+        .sum()
+}
+
+fn neighbors(c: &mut Criterion) {
+    let map = maze(&mut Rng::new(2019), SIZE, SIZE, SIZE * 10);
+    let nested: Vec<Vec<bool>> = map
+        .lines()
+        .map(|line| line.chars().map(|c| c == '.').collect())
+        .collect();
+    let grid = Grid::parse(&map, |c| c == '.');
+
+    let mut group = c.benchmark_group("open_neighbors");
+    group.bench_function("nested", |b| {
+        b.iter(|| open_neighbors(grid.points(), |p| nested_get(black_box(&nested), p)))
+    });
+    group.bench_function("grid", |b| {
+        b.iter(|| open_neighbors(grid.points(), |p| black_box(&grid).get(p).cloned()))
+    });
+    group.bench_function("grid_neighbors4", |b| {
+        b.iter(|| {
+            let grid = black_box(&grid);
+            grid.enumerate_cells()
+                .filter(|&(_, &open)| open)
+                .map(|(p, _)| grid.neighbors4(p).filter(|&q| grid[q]).count())
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, neighbors);
+criterion_main!(benches);
@@ -0,0 +1,84 @@
+//! The hasher behind `aoc_utils::collections`, FxHash, against std's SipHash
+//! on the keys the grid and search code hash most: points. Running the
+//! `search` benches with and without `--features siphash` compares the two
+//! inside the library searches as well.
+
+use aoc_utils::genlib::{asteroid_field, maze, Rng};
+use aoc_utils::geom::{direction, Point};
+use aoc_utils::grid::Grid;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rustc_hash::FxHasher;
+use std::collections::hash_map::{Entry, RandomState};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{BuildHasher, BuildHasherDefault};
+
+type Fx = BuildHasherDefault<FxHasher>;
+
+/// Distances from the corner of a maze, as `search::bfs` finds them.
+fn maze_distances<S>(grid: &Grid<bool>) -> usize
+where
+    S: BuildHasher + Default,
+{
+    let mut distances: HashMap<Point, usize, S> = HashMap::default();
+    let mut active = VecDeque::new();
+    distances.insert((1, 1), 0);
+    active.push_back(((1, 1), 0));
+    while let Some((p, n)) = active.pop_front() {
+        for q in grid.neighbors4(p).filter(|&q| grid[q]) {
+            if let Entry::Vacant(entry) = distances.entry(q) {
+                entry.insert(n + 1);
+                active.push_back((q, n + 1));
+            }
+        }
+    }
+    distances.len()
+}
+
+/// Day 10's best station: the most distinct directions to other asteroids
+/// from any one asteroid.
+fn most_visible<S>(asteroids: &[Point]) -> usize
+where
+    S: BuildHasher + Default,
+{
+    asteroids
+        .iter()
+        .map(|&station| {
+            asteroids
+                .iter()
+                .filter(|&&a| a != station)
+                .map(|&a| direction(station, a))
+                .collect::<HashSet<Point, S>>()
+                .len()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn hashers(c: &mut Criterion) {
+    let map = maze(&mut Rng::new(2019), 401, 401, 401);
+    let grid = Grid::parse(&map, |c| c == '.');
+    let mut group = c.benchmark_group("maze_distances");
+    group.bench_function("fx", |b| b.iter(|| maze_distances::<Fx>(black_box(&grid))));
+    group.bench_function("sip", |b| {
+        b.iter(|| maze_distances::<RandomState>(black_box(&grid)))
+    });
+    group.finish();
+
+    let field = asteroid_field(&mut Rng::new(10), 40, 40, 0.2);
+    let asteroids: Vec<Point> = Grid::parse(&field, |c| c == '#')
+        .enumerate_cells()
+        .filter(|&(_, &asteroid)| asteroid)
+        .map(|(p, _)| p)
+        .collect();
+    let mut group = c.benchmark_group("most_visible");
+    group.bench_function("fx", |b| {
+        b.iter(|| most_visible::<Fx>(black_box(&asteroids)))
+    });
+    group.bench_function("sip", |b| {
+        b.iter(|| most_visible::<RandomState>(black_box(&asteroids)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, hashers);
+criterion_main!(benches);
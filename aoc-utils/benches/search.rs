@@ -0,0 +1,66 @@
+//! Searches over a large maze like those of days 18 and 20, with neighbors
+//! pushed straight into the search versus first collected into a `Vec` per
+//! expansion, which is what a callback returning a collection costs.
+
+use aoc_utils::genlib::{maze, Rng};
+use aoc_utils::geom::Point;
+use aoc_utils::grid::Grid;
+use aoc_utils::search::{bfs, contract};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const SIZE: usize = 401;
+
+fn open_neighbors(grid: &Grid<bool>, p: Point) -> impl Iterator<Item = Point> + '_ {
+    grid.neighbors4(p).filter(move |&q| grid[q])
+}
+
+fn searches(c: &mut Criterion) {
+    let map = maze(&mut Rng::new(2019), SIZE, SIZE, SIZE);
+    let grid = Grid::parse(&map, |c| c == '.');
+    let start = (1, 1);
+
+    let mut group = c.benchmark_group("bfs");
+    group.bench_function("push", |b| {
+        let grid = black_box(&grid);
+        b.iter(|| bfs(start, |&p, push| open_neighbors(grid, p).for_each(push)))
+    });
+    group.bench_function("vec", |b| {
+        let grid = black_box(&grid);
+        b.iter(|| {
+            bfs(start, |&p, push| {
+                let next: Vec<Point> = open_neighbors(grid, p).collect();
+                next.into_iter().for_each(push)
+            })
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("contract");
+    group.bench_function("push", |b| {
+        let grid = black_box(&grid);
+        b.iter(|| {
+            contract(
+                start,
+                |&p, push| open_neighbors(grid, p).for_each(push),
+                |_| false,
+            )
+        })
+    });
+    group.bench_function("vec", |b| {
+        let grid = black_box(&grid);
+        b.iter(|| {
+            contract(
+                start,
+                |&p, push| {
+                    let next: Vec<Point> = open_neighbors(grid, p).collect();
+                    next.into_iter().for_each(push)
+                },
+                |_| false,
+            )
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, searches);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+//! Hash maps and sets used by the searches. They default to FxHash, which is
+//! much quicker than std's SipHash on the small keys searches use and hashes
+//! the same way every run, so timings are reproducible. Enable the `siphash`
+//! feature to switch back to std's hasher, e.g. to compare the two; the
+//! `hashers` bench compares them directly.
+
+use std::collections;
+
+#[cfg(not(feature = "siphash"))]
+pub type BuildHasher = std::hash::BuildHasherDefault<rustc_hash::FxHasher>;
+#[cfg(feature = "siphash")]
+pub type BuildHasher = collections::hash_map::RandomState;
+
+pub type HashMap<K, V> = collections::HashMap<K, V, BuildHasher>;
+pub type HashSet<T> = collections::HashSet<T, BuildHasher>;
@@ -0,0 +1,134 @@
+//! Synthetic puzzle inputs for stress-testing solvers and the library
+//! algorithms on more than the one official input. Everything is driven by a
+//! seeded `Rng`, so a failing case can be reproduced from its seed.
+
+/// A small, fast PRNG (Knuth's MMIX LCG, high bits only). Not for anything
+/// that needs real randomness.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 32) as u32
+    }
+
+    /// A number in `0..n`. Panics if `n` is zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "empty range");
+        ((u64::from(self.next_u32()) * n as u64) >> 32) as usize
+    }
+
+    /// True with probability `p`.
+    pub fn chance(&mut self, p: f64) -> bool {
+        f64::from(self.next_u32()) < p * f64::from(u32::MAX)
+    }
+}
+
+/// An asteroid field in the day 10 format: `height` lines of `width` cells,
+/// each an asteroid (`#`) with probability `density`.
+pub fn asteroid_field(rng: &mut Rng, width: usize, height: usize, density: f64) -> String {
+    let mut field = String::with_capacity((width + 1) * height);
+    for _ in 0..height {
+        for _ in 0..width {
+            field.push(if rng.chance(density) { '#' } else { '.' });
+        }
+        field.push('\n');
+    }
+    field
+}
+
+/// A maze of walls (`#`) and open cells (`.`), surrounded by wall, in which
+/// every open cell can be reached from every other. Without `loops` it's a
+/// perfect maze (exactly one path between any two cells); each loop knocks
+/// out one more interior wall, giving searches a choice of routes.
+///
+/// Open cells sit at odd coordinates, so even sizes are rounded down to odd.
+/// Panics if either size is less than 3.
+pub fn maze(rng: &mut Rng, width: usize, height: usize, loops: usize) -> String {
+    assert!(width >= 3 && height >= 3, "maze must be at least 3x3");
+    let width = width - (1 - width % 2);
+    let height = height - (1 - height % 2);
+    let mut cells = vec![vec![b'#'; width]; height];
+
+    // A randomized depth-first carve from (1, 1) reaches every odd cell.
+    let mut stack = vec![(1, 1)];
+    cells[1][1] = b'.';
+    while let Some(&(x, y)) = stack.last() {
+        let unvisited: Vec<(usize, usize)> = [(2, 0), (0, 2), (-2, 0), (0, -2)]
+            .iter()
+            .map(|&(dx, dy)| ((x as isize + dx) as usize, (y as isize + dy) as usize))
+            .filter(|&(nx, ny)| nx < width - 1 && ny < height - 1 && cells[ny][nx] == b'#')
+            .collect();
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let (nx, ny) = unvisited[rng.below(unvisited.len())];
+        cells[(y + ny) / 2][(x + nx) / 2] = b'.';
+        cells[ny][nx] = b'.';
+        stack.push((nx, ny));
+    }
+
+    // Walls between two open cells, either horizontally or vertically.
+    let mut walls: Vec<(usize, usize)> = (1..height - 1)
+        .flat_map(|y| (1..width - 1).map(move |x| (x, y)))
+        .filter(|&(x, y)| (x + y) % 2 == 1 && cells[y][x] == b'#')
+        .collect();
+    for _ in 0..loops.min(walls.len()) {
+        let (x, y) = walls.swap_remove(rng.below(walls.len()));
+        cells[y][x] = b'.';
+    }
+
+    let mut maze = String::with_capacity((width + 1) * height);
+    for row in cells {
+        maze.extend(row.into_iter().map(char::from));
+        maze.push('\n');
+    }
+    maze
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::search::bfs;
+
+    #[test]
+    fn mazes_are_connected() {
+        let mut rng = Rng::new(2019);
+        for &(width, height, loops) in &[(3, 3, 0), (21, 11, 0), (40, 30, 25)] {
+            let maze = maze(&mut rng, width, height, loops);
+            let grid = Grid::parse(&maze, |c| c == '.');
+            let open = grid.cells().filter(|&&open| open).count();
+            let reached = bfs((1, 1), |&p, push| {
+                grid.neighbors4(p).filter(|&q| grid[q]).for_each(push)
+            });
+            assert_eq!(reached.len(), open, "{}", maze);
+
+            // A perfect maze on an odd grid carves exactly one passage per
+            // cell after the first.
+            let (w, h) = (width - (1 - width % 2), height - (1 - height % 2));
+            let cells = (w / 2) * (h / 2);
+            assert_eq!(open, 2 * cells - 1 + loops);
+        }
+    }
+
+    #[test]
+    fn asteroid_field_shape() {
+        let field = asteroid_field(&mut Rng::new(10), 30, 20, 0.25);
+        let lines: Vec<&str> = field.lines().collect();
+        assert_eq!(lines.len(), 20);
+        assert!(lines.iter().all(|line| line.len() == 30));
+        let asteroids = field.matches('#').count();
+        assert!(asteroids > 100 && asteroids < 200, "{}", asteroids);
+        assert_eq!(field, asteroid_field(&mut Rng::new(10), 30, 20, 0.25));
+    }
+}
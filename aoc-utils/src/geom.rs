@@ -0,0 +1,577 @@
+use itertools::Itertools;
+use num::Integer;
+use std::cmp::Ordering;
+use std::iter;
+
+pub type Point = (i64, i64);
+
+/// Reduces the offset from `origin` to `p` to its smallest integer step, so
+/// that every point on the same ray from `origin` shares a direction.
+pub fn direction(origin: Point, p: Point) -> Point {
+    let dx = p.0 - origin.0;
+    let dy = p.1 - origin.1;
+    let gcd = dx.gcd(&dy);
+    if gcd == 0 {
+        (0, 0)
+    } else {
+        (dx / gcd, dy / gcd)
+    }
+}
+
+/// Angle of `p` around `origin`, measured clockwise from "up" (negative y) in
+/// screen coordinates, in the range [-pi, pi).
+pub fn clockwise_angle(origin: Point, p: Point) -> f64 {
+    let x = (p.0 - origin.0) as f64;
+    let y = (p.1 - origin.1) as f64;
+    // This is a strange formulation:
+    //  - x.atan2(y) rotates us so that the -pi/pi boundary point is "up"
+    //  - negating the whole thing gives us the clockwise rotation we need
+    -(x.atan2(y))
+}
+
+/// Orders `a` and `b` by `clockwise_angle` around `origin`, but exactly,
+/// with integer cross products instead of floating-point angles. `origin`
+/// itself sorts along with the points straight down from it, as it does by
+/// angle.
+pub fn clockwise_cmp(origin: Point, a: Point, b: Point) -> Ordering {
+    // Up and everything clockwise of it short of straight down, then the rest.
+    let half = |(dx, dy): Wide| if dx > 0 || (dx == 0 && dy < 0) { 0 } else { 1 };
+    let offset = |p: Point| match wide_sub(p, origin) {
+        (0, 0) => (0, 1),
+        d => d,
+    };
+    let (a, b) = (offset(a), offset(b));
+    half(a).cmp(&half(b)).then_with(|| cross_sign(b, a))
+}
+
+/// Which way the path from `a` through `b` to `c` turns, as seen on a
+/// screen-oriented grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    Clockwise,
+    Counterclockwise,
+    Collinear,
+}
+
+/// Exact for any coordinates, however far apart.
+pub fn orientation(a: Point, b: Point, c: Point) -> Orientation {
+    match cross_sign(wide_sub(b, a), wide_sub(c, a)) {
+        Ordering::Greater => Orientation::Clockwise,
+        Ordering::Less => Orientation::Counterclockwise,
+        Ordering::Equal => Orientation::Collinear,
+    }
+}
+
+/// Whether `p` lies on the segment from `a` to `b`, endpoints included.
+pub fn on_segment(p: Point, (a, b): (Point, Point)) -> bool {
+    orientation(a, b, p) == Orientation::Collinear
+        && a.0.min(b.0) <= p.0
+        && p.0 <= a.0.max(b.0)
+        && a.1.min(b.1) <= p.1
+        && p.1 <= a.1.max(b.1)
+}
+
+/// Whether two segments share at least one point, counting endpoints and
+/// collinear overlaps. A segment may be a single point.
+pub fn segments_intersect(s: (Point, Point), t: (Point, Point)) -> bool {
+    let (s1, t1) = (orientation(s.0, s.1, t.0), orientation(s.0, s.1, t.1));
+    let (s2, t2) = (orientation(t.0, t.1, s.0), orientation(t.0, t.1, s.1));
+    (s1 != t1 && s2 != t2)
+        || on_segment(t.0, s)
+        || on_segment(t.1, s)
+        || on_segment(s.0, t)
+        || on_segment(s.1, t)
+}
+
+// Differences between any two points fit comfortably in an i128.
+type Wide = (i128, i128);
+
+fn wide_sub(p: Point, q: Point) -> Wide {
+    (
+        i128::from(p.0) - i128::from(q.0),
+        i128::from(p.1) - i128::from(q.1),
+    )
+}
+
+// The sign of the cross product of `u` and `v`, as the comparison of its two
+// terms. Those can need up to 129 bits, so very distant points fall back to
+// big integers.
+fn cross_sign(u: Wide, v: Wide) -> Ordering {
+    match (u.0.checked_mul(v.1), u.1.checked_mul(v.0)) {
+        (Some(left), Some(right)) => left.cmp(&right),
+        _ => {
+            let big = num::BigInt::from;
+            (big(u.0) * big(v.1)).cmp(&(big(u.1) * big(v.0)))
+        }
+    }
+}
+
+/// Taxicab distance: the fewest 4-connected grid steps between two points, so
+/// never more than the real path length through a maze. Overflow panics in
+/// debug builds; use `checked_manhattan` for points that may be far apart.
+pub fn manhattan(origin: Point, p: Point) -> i64 {
+    (p.0 - origin.0).abs() + (p.1 - origin.1).abs()
+}
+
+pub fn checked_manhattan(origin: Point, p: Point) -> Option<i64> {
+    let (dx, dy) = checked_sub(p, origin)?;
+    dx.checked_abs()?.checked_add(dy.checked_abs()?)
+}
+
+/// `p + q`, or `None` if either coordinate overflows.
+pub fn checked_add(p: Point, q: Point) -> Option<Point> {
+    Some((p.0.checked_add(q.0)?, p.1.checked_add(q.1)?))
+}
+
+/// `p - q`, or `None` if either coordinate overflows.
+pub fn checked_sub(p: Point, q: Point) -> Option<Point> {
+    Some((p.0.checked_sub(q.0)?, p.1.checked_sub(q.1)?))
+}
+
+/// The dot product, widened to `i128`. That's exact unless all four
+/// coordinates are `i64::MIN`, which overflows (and panics in debug builds).
+pub fn dot(a: Point, b: Point) -> i128 {
+    i128::from(a.0) * i128::from(b.0) + i128::from(a.1) * i128::from(b.1)
+}
+
+/// The z component of the cross product, widened to `i128`, where it's always
+/// exact. Positive when `b` is clockwise of `a` on a screen-oriented grid.
+pub fn cross(a: Point, b: Point) -> i128 {
+    i128::from(a.0) * i128::from(b.1) - i128::from(a.1) * i128::from(b.0)
+}
+
+/// A compass direction on a screen-oriented grid, where y grows downward, so
+/// north is `(0, -1)`. Each puzzle's own encoding of directions converts to
+/// and from this in `advent2019::protocol`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    /// Clockwise from north.
+    pub const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    pub fn offset(self) -> Point {
+        match self {
+            Direction::North => (0, -1),
+            Direction::East => (1, 0),
+            Direction::South => (0, 1),
+            Direction::West => (-1, 0),
+        }
+    }
+
+    /// The point one step from `p` in this direction.
+    pub fn step(self, p: Point) -> Point {
+        let (dx, dy) = self.offset();
+        (p.0 + dx, p.1 + dy)
+    }
+
+    /// Like `step`, but `None` at the edge of the coordinate space.
+    pub fn checked_step(self, p: Point) -> Option<Point> {
+        checked_add(p, self.offset())
+    }
+
+    pub fn turn_left(self) -> Direction {
+        Direction::ALL[(self as usize + 3) % 4]
+    }
+
+    pub fn turn_right(self) -> Direction {
+        Direction::ALL[(self as usize + 1) % 4]
+    }
+
+    pub fn reverse(self) -> Direction {
+        Direction::ALL[(self as usize + 2) % 4]
+    }
+}
+
+/// Follows a path given as runs of steps, like day 3's wires, yielding each
+/// point it visits (not including `start`) with the number of steps taken to
+/// get there. Points the path crosses more than once come up each time.
+pub fn trace_path<I>(start: Point, moves: I) -> impl Iterator<Item = (Point, usize)>
+where
+    I: IntoIterator<Item = (Direction, usize)>,
+{
+    moves
+        .into_iter()
+        .flat_map(|(direction, steps)| iter::repeat_n(direction, steps))
+        .scan((start, 0), |(p, steps), direction| {
+            *p = direction.step(*p);
+            *steps += 1;
+            Some((*p, *steps))
+        })
+}
+
+/// One straight run of a path, from `from` to `to` inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PathSegment {
+    pub from: Point,
+    pub to: Point,
+    /// Steps taken along the path before `from`.
+    pub steps: usize,
+}
+
+impl PathSegment {
+    /// Steps taken along the path to reach `p`, which must be on this segment.
+    pub fn steps_to(&self, p: Point) -> usize {
+        debug_assert!(
+            on_segment(p, (self.from, self.to)),
+            "{:?} is off {:?}",
+            p,
+            self
+        );
+        self.steps + manhattan(self.from, p) as usize
+    }
+
+    /// The points this segment shares with `other`: none, one where they
+    /// cross or touch, or a run of them where they overlap.
+    pub fn crossings(&self, other: &PathSegment) -> impl Iterator<Item = Point> {
+        // Both segments run along an axis, so each is its own bounding box,
+        // and what they share is the overlap of the boxes: empty if they
+        // don't meet.
+        let overlap =
+            |a: i64, b: i64, c: i64, d: i64| (a.min(b).max(c.min(d)), a.max(b).min(c.max(d)));
+        let (x0, x1) = overlap(self.from.0, self.to.0, other.from.0, other.to.0);
+        let (y0, y1) = overlap(self.from.1, self.to.1, other.from.1, other.to.1);
+        (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+    }
+}
+
+/// Like `trace_path`, but yields a segment per run instead of every point,
+/// for finding where paths meet without visiting each step.
+pub fn path_segments<I>(start: Point, moves: I) -> impl Iterator<Item = PathSegment>
+where
+    I: IntoIterator<Item = (Direction, usize)>,
+{
+    moves
+        .into_iter()
+        .scan((start, 0), |(p, steps), (direction, n)| {
+            let (dx, dy) = direction.offset();
+            let to = (p.0 + dx * n as i64, p.1 + dy * n as i64);
+            let segment = PathSegment {
+                from: *p,
+                to,
+                steps: *steps,
+            };
+            *p = to;
+            *steps += n;
+            Some(segment)
+        })
+}
+
+pub trait PointIterExt: Iterator<Item = Point> + Sized {
+    /// Sorts points clockwise around `origin` starting from "up", nearest
+    /// first along each ray. Only copies of the same point tie, so the result
+    /// is the same whatever order the points came in, with any duplicates
+    /// next to each other.
+    fn sorted_by_angle_around(self, origin: Point) -> std::vec::IntoIter<Point> {
+        self.sorted_by(|&a, &b| {
+            clockwise_cmp(origin, a, b)
+                .then_with(|| manhattan(origin, a).cmp(&manhattan(origin, b)))
+        })
+    }
+
+    /// Groups consecutive points that lie on the same ray from `origin`. Fed
+    /// from `sorted_by_angle_around`, each group is one sweep direction with
+    /// its points ordered nearest first (duplicates included). `origin`
+    /// itself, if present, gets a group of its own.
+    fn group_by_direction(self, origin: Point) -> Vec<Vec<Point>> {
+        self.group_by(|&p| direction(origin, p))
+            .into_iter()
+            .map(|(_, group)| group.collect())
+            .collect()
+    }
+}
+
+impl<I> PointIterExt for I where I: Iterator<Item = Point> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::iproduct;
+    use num::BigInt;
+    use proptest::prelude::*;
+    use std::convert::TryFrom;
+
+    fn fits(n: i128) -> bool {
+        i64::try_from(n).is_ok()
+    }
+
+    #[test]
+    fn cross_sign() {
+        // North to east is a clockwise turn with y growing downward.
+        assert!(cross(Direction::North.offset(), Direction::East.offset()) > 0);
+        assert!(cross(Direction::East.offset(), Direction::North.offset()) < 0);
+        assert_eq!(cross((2, 4), (1, 2)), 0);
+    }
+
+    #[test]
+    fn extremes() {
+        let (min, max) = (i64::MIN, i64::MAX);
+        assert_eq!(checked_add((max, 0), (1, 0)), None);
+        assert_eq!(checked_sub((0, min), (0, 1)), None);
+        assert_eq!(checked_manhattan((0, 0), (min, 0)), None);
+        assert_eq!(checked_manhattan((max, 0), (-1, 0)), None);
+        assert_eq!(checked_manhattan((0, 0), (max, 0)), Some(max));
+        assert_eq!(Direction::North.checked_step((0, min)), None);
+        assert_eq!(Direction::South.checked_step((0, min)), Some((0, min + 1)));
+        assert_eq!(cross((min, max), (max, min)), (1i128 << 64) - 1);
+        assert_eq!(dot((min, min), (min, max)), (1i128 << 63));
+    }
+
+    fn small_points() -> impl Iterator<Item = Point> + Clone {
+        iproduct!(-2..=2, -2..=2)
+    }
+
+    #[test]
+    fn orientation_exhaustive() {
+        for (a, b, c) in iproduct!(small_points(), small_points(), small_points()) {
+            let turn = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+            let expected = match turn.signum() {
+                1 => Orientation::Clockwise,
+                -1 => Orientation::Counterclockwise,
+                _ => Orientation::Collinear,
+            };
+            assert_eq!(orientation(a, b, c), expected, "{:?}", (a, b, c));
+        }
+    }
+
+    #[test]
+    fn orientation_far_apart() {
+        let (min, max) = (i64::MIN, i64::MAX);
+        assert_eq!(
+            orientation((min, min), (max, max), (min, min + 1)),
+            Orientation::Clockwise
+        );
+        assert_eq!(
+            orientation((min, min), (max, max), (min + 1, min)),
+            Orientation::Counterclockwise
+        );
+        assert_eq!(
+            orientation((min, min), (0, 0), (max, max)),
+            Orientation::Collinear
+        );
+    }
+
+    // Solves for the intersection parametrically instead, with the parallel
+    // case handled by projecting onto the line.
+    fn brute_force_intersect(s: (Point, Point), t: (Point, Point)) -> bool {
+        let sub = |p: Point, q: Point| (p.0 - q.0, p.1 - q.1);
+        let cross = |u: Point, v: Point| u.0 * v.1 - u.1 * v.0;
+        let dot = |u: Point, v: Point| u.0 * v.0 + u.1 * v.1;
+        let (r, q) = (sub(s.1, s.0), sub(t.1, t.0));
+        let w = sub(t.0, s.0);
+        let d = cross(r, q);
+        if d != 0 {
+            // s.0 + r * (n1 / d) == t.0 + q * (n2 / d)
+            let (n1, n2) = (cross(w, q), cross(w, r));
+            let within = |n: i64| {
+                if d > 0 {
+                    0 <= n && n <= d
+                } else {
+                    d <= n && n <= 0
+                }
+            };
+            return within(n1) && within(n2);
+        }
+        if cross(w, r) != 0 || cross(w, q) != 0 {
+            return false;
+        }
+        // Collinear (or points): project onto whichever segment has length.
+        let (base, dir, other) = if r != (0, 0) {
+            (s.0, r, t)
+        } else {
+            (t.0, q, s)
+        };
+        if dir == (0, 0) {
+            return s.0 == t.0;
+        }
+        let len = dot(dir, dir);
+        let (p0, p1) = (dot(sub(other.0, base), dir), dot(sub(other.1, base), dir));
+        p0.min(p1) <= len && p0.max(p1) >= 0
+    }
+
+    #[test]
+    fn segments_intersect_exhaustive() {
+        let segments: Vec<(Point, Point)> = iproduct!(small_points(), small_points()).collect();
+        for &s in &segments {
+            for &t in &segments {
+                assert_eq!(
+                    segments_intersect(s, t),
+                    brute_force_intersect(s, t),
+                    "{:?} {:?}",
+                    s,
+                    t
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn clockwise_cmp_matches_angles() {
+        let origin = (0, 0);
+        for (a, b) in iproduct!(small_points(), small_points()) {
+            let (x, y) = (clockwise_angle(origin, a), clockwise_angle(origin, b));
+            let expected = if (x - y).abs() < 1e-9 {
+                Ordering::Equal
+            } else {
+                x.partial_cmp(&y).unwrap()
+            };
+            assert_eq!(clockwise_cmp(origin, a, b), expected, "{:?} {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn sorted_by_angle() {
+        let ring = [
+            (0, -1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-1, -1),
+        ];
+        let shuffled = [4, 7, 0, 2, 6, 1, 5, 3].iter().map(|&i| ring[i]);
+        assert_eq!(
+            shuffled.sorted_by_angle_around((0, 0)).collect::<Vec<_>>(),
+            ring
+        );
+        // Around some other origin, farther along each ray comes later.
+        let origin = (5, 5);
+        let points = vec![(5, 3), (6, 5), (5, 4), (7, 5), (4, 4)];
+        assert_eq!(
+            points
+                .into_iter()
+                .sorted_by_angle_around(origin)
+                .collect::<Vec<_>>(),
+            [(5, 4), (5, 3), (6, 5), (7, 5), (4, 4)]
+        );
+    }
+
+    #[test]
+    fn grouped_by_direction() {
+        let points = vec![(0, -3), (2, 0), (0, -1), (2, 2), (1, 0), (0, -2), (-3, 6)];
+        let groups = points
+            .into_iter()
+            .sorted_by_angle_around((0, 0))
+            .group_by_direction((0, 0));
+        assert_eq!(
+            groups,
+            [
+                vec![(0, -1), (0, -2), (0, -3)],
+                vec![(1, 0), (2, 0)],
+                vec![(2, 2)],
+                vec![(-3, 6)],
+            ]
+        );
+    }
+
+    #[test]
+    fn angle_order_ignores_input_order() {
+        use itertools::Itertools;
+
+        // Duplicates, the origin itself, and points sharing a ray.
+        let points = [(1, 0), (0, 0), (2, 0), (1, 0), (0, 2), (-1, -1)];
+        let expected = [(1, 0), (1, 0), (2, 0), (0, 0), (0, 2), (-1, -1)];
+        for order in points.iter().cloned().permutations(points.len()) {
+            let sorted: Vec<Point> = order.into_iter().sorted_by_angle_around((0, 0)).collect();
+            assert_eq!(sorted, expected);
+            assert_eq!(
+                sorted.into_iter().group_by_direction((0, 0)),
+                [
+                    vec![(1, 0), (1, 0), (2, 0)],
+                    vec![(0, 0)],
+                    vec![(0, 2)],
+                    vec![(-1, -1)],
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn tracing_and_segments_agree() {
+        use Direction::*;
+        let moves = [(East, 3), (South, 2), (West, 0), (North, 4), (West, 5)];
+        let points: Vec<(Point, usize)> = trace_path((1, 1), moves.iter().cloned()).collect();
+        assert_eq!(points.len(), 14);
+        assert_eq!(
+            &points[..4],
+            [((2, 1), 1), ((3, 1), 2), ((4, 1), 3), ((4, 2), 4)]
+        );
+        assert_eq!(points.last(), Some(&((-1, -1), 14)));
+
+        let segments: Vec<PathSegment> = path_segments((1, 1), moves.iter().cloned()).collect();
+        assert_eq!(segments.len(), 5);
+        for &(p, steps) in &points {
+            let on: Vec<usize> = segments
+                .iter()
+                .filter(|s| on_segment(p, (s.from, s.to)))
+                .map(|s| s.steps_to(p))
+                .collect();
+            assert!(on.contains(&steps), "{:?} {} {:?}", p, steps, on);
+        }
+    }
+
+    #[test]
+    fn segment_crossings() {
+        let segment = |from, to| PathSegment { from, to, steps: 0 };
+        let crossings = |s: PathSegment, t| s.crossings(&t).collect::<Vec<_>>();
+        let horizontal = segment((0, 2), (6, 2));
+        assert_eq!(crossings(horizontal, segment((3, 0), (3, 5))), [(3, 2)]);
+        assert_eq!(crossings(horizontal, segment((6, 2), (6, 9))), [(6, 2)]);
+        assert_eq!(crossings(horizontal, segment((7, 0), (7, 5))), []);
+        assert_eq!(crossings(horizontal, segment((3, 3), (3, 5))), []);
+        assert_eq!(
+            crossings(horizontal, segment((8, 2), (4, 2))),
+            [(4, 2), (5, 2), (6, 2)]
+        );
+        assert_eq!(crossings(horizontal, segment((0, 3), (6, 3))), []);
+    }
+
+    proptest! {
+        #[test]
+        fn checked_ops_match_wide_math(a: (i64, i64), b: (i64, i64)) {
+            let sum = (i128::from(a.0) + i128::from(b.0), i128::from(a.1) + i128::from(b.1));
+            let expected = if fits(sum.0) && fits(sum.1) {
+                Some((sum.0 as i64, sum.1 as i64))
+            } else {
+                None
+            };
+            prop_assert_eq!(checked_add(a, b), expected);
+
+            let distance = (i128::from(a.0) - i128::from(b.0)).abs()
+                + (i128::from(a.1) - i128::from(b.1)).abs();
+            let expected = if fits(distance) { Some(distance as i64) } else { None };
+            prop_assert_eq!(checked_manhattan(b, a), expected);
+        }
+
+        #[test]
+        fn products_are_exact(a: (i64, i64), b: (i64, i64)) {
+            let big = |n: i64| BigInt::from(n);
+            let cross_big = big(a.0) * big(b.1) - big(a.1) * big(b.0);
+            prop_assert_eq!(BigInt::from(cross(a, b)), cross_big);
+            prop_assert_eq!(cross(a, b), -cross(b, a));
+            if a != (i64::MIN, i64::MIN) || b != a {
+                let dot_big = big(a.0) * big(b.0) + big(a.1) * big(b.1);
+                prop_assert_eq!(BigInt::from(dot(a, b)), dot_big);
+            }
+        }
+
+        #[test]
+        fn small_coordinates_agree_with_i64(a in (-1000i64..1000, -1000i64..1000), b in (-1000i64..1000, -1000i64..1000)) {
+            prop_assert_eq!(dot(a, b), i128::from(a.0 * b.0 + a.1 * b.1));
+            prop_assert_eq!(cross(a, b), i128::from(a.0 * b.1 - a.1 * b.0));
+            prop_assert_eq!(checked_manhattan(a, b), Some(manhattan(a, b)));
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Dense rectangular grids, like the puzzle maps, addressed by `geom::Point`.
+
+use crate::geom::Point;
+use std::ops::{Index, IndexMut};
+
+const NEIGHBORS4: [Point; 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+const NEIGHBORS8: [Point; 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// A `width` x `height` grid stored row-major in one `Vec`, so walking a row
+/// or looking at neighbors stays within a single allocation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Grid<T>
+    where
+        T: Clone,
+    {
+        Grid {
+            cells: vec![fill; width * height],
+            width,
+            height,
+        }
+    }
+
+    /// Builds a grid from its rows. Panics if they aren't all the same length.
+    pub fn from_rows<R>(rows: impl IntoIterator<Item = R>) -> Grid<T>
+    where
+        R: IntoIterator<Item = T>,
+    {
+        let mut cells = Vec::new();
+        let mut width = None;
+        let mut height = 0;
+        for row in rows {
+            let start = cells.len();
+            cells.extend(row);
+            let len = cells.len() - start;
+            assert_eq!(*width.get_or_insert(len), len, "row {} is ragged", height);
+            height += 1;
+        }
+        Grid {
+            cells,
+            width: width.unwrap_or(0),
+            height,
+        }
+    }
+
+    /// Parses a puzzle map, one line per row, converting each character with
+    /// `cell`.
+    pub fn parse(input: &str, mut cell: impl FnMut(char) -> T) -> Grid<T> {
+        Grid::from_rows(
+            input
+                .lines()
+                .map(|line| line.chars().map(&mut cell).collect::<Vec<_>>()),
+        )
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, p: Point) -> bool {
+        self.index(p).is_some()
+    }
+
+    // The one bounds check every accessor goes through; after it, indexing
+    // `cells` can't fail.
+    fn index(&self, (x, y): Point) -> Option<usize> {
+        if x >= 0 && y >= 0 && (x as u64) < self.width as u64 && (y as u64) < self.height as u64 {
+            Some(y as usize * self.width + x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, p: Point) -> Option<&T> {
+        self.index(p).map(|i| &self.cells[i])
+    }
+
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
+        self.index(p).map(move |i| &mut self.cells[i])
+    }
+
+    /// Row `y` as a slice, for hot loops that would otherwise pay for a
+    /// bounds check on every cell. Panics if `y` is out of range.
+    pub fn row(&self, y: usize) -> &[T] {
+        assert!(y < self.height, "row {} of {}", y, self.height);
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        assert!(y < self.height, "row {} of {}", y, self.height);
+        &mut self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    /// Every point in the grid, row by row.
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        let (width, height) = (self.width as i64, self.height as i64);
+        (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    /// The 4-connected neighbors of `p` that are inside the grid, clockwise
+    /// from north (as in `Direction::ALL`).
+    pub fn neighbors4(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        self.neighbors(p, &NEIGHBORS4)
+    }
+
+    /// Like `neighbors4`, but with the diagonals as well, clockwise from north.
+    pub fn neighbors8(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        self.neighbors(p, &NEIGHBORS8)
+    }
+
+    fn neighbors(
+        &self,
+        (x, y): Point,
+        offsets: &'static [Point],
+    ) -> impl Iterator<Item = Point> + '_ {
+        offsets
+            .iter()
+            .filter_map(move |&(dx, dy)| Some((x.checked_add(dx)?, y.checked_add(dy)?)))
+            .filter(move |&q| self.contains(q))
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.height).map(move |y| self.row(y))
+    }
+
+    /// Every cell, row by row.
+    pub fn cells(&self) -> std::slice::Iter<'_, T> {
+        self.cells.iter()
+    }
+
+    pub fn cells_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.cells.iter_mut()
+    }
+
+    /// Every cell along with its point, row by row.
+    pub fn enumerate_cells(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.points().zip(&self.cells)
+    }
+}
+
+/// Indexing is for points already known to be in bounds, and panics
+/// otherwise; use `get` when they might not be.
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, p: Point) -> &T {
+        match self.get(p) {
+            Some(cell) => cell,
+            None => panic!("{:?} is outside a {}x{} grid", p, self.width, self.height),
+        }
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, p: Point) -> &mut T {
+        let (width, height) = (self.width, self.height);
+        match self.get_mut(p) {
+            Some(cell) => cell,
+            None => panic!("{:?} is outside a {}x{} grid", p, width, height),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_access() {
+        let mut grid = Grid::parse("#..\n.#.\n", |c| c == '#');
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.get((0, 0)), Some(&true));
+        assert_eq!(grid.get((1, 1)), Some(&true));
+        assert_eq!(grid.get((2, 1)), Some(&false));
+        for &p in &[(3, 0), (0, 2), (-1, 0), (0, -1), (i64::MIN, i64::MAX)] {
+            assert!(!grid.contains(p));
+            assert_eq!(grid.get(p), None);
+        }
+
+        *grid.get_mut((2, 1)).unwrap() = true;
+        assert_eq!(grid.row(1), [false, true, true]);
+        assert_eq!(
+            grid.points()
+                .filter(|&p| grid.get(p) == Some(&true))
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn indexing_and_iteration() {
+        let mut grid = Grid::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        assert_eq!(grid[(2, 0)], 3);
+        grid[(0, 1)] = 40;
+        assert_eq!(grid.rows().collect::<Vec<_>>(), [[1, 2, 3], [40, 5, 6]]);
+        assert_eq!(grid.cells().sum::<i32>(), 57);
+        assert_eq!(
+            grid.enumerate_cells().find(|&(_, &n)| n == 5),
+            Some(((1, 1), &5))
+        );
+    }
+
+    #[test]
+    fn neighbors_at_edges() {
+        let grid = Grid::new(3, 3, ());
+        let four = |p| grid.neighbors4(p).collect::<Vec<_>>();
+        let eight = |p| grid.neighbors8(p).collect::<Vec<_>>();
+
+        assert_eq!(four((1, 1)), [(1, 0), (2, 1), (1, 2), (0, 1)]);
+        assert_eq!(eight((1, 1)).len(), 8);
+        // Corners.
+        assert_eq!(four((0, 0)), [(1, 0), (0, 1)]);
+        assert_eq!(eight((0, 0)), [(1, 0), (1, 1), (0, 1)]);
+        assert_eq!(four((2, 2)), [(2, 1), (1, 2)]);
+        assert_eq!(eight((2, 2)), [(2, 1), (1, 2), (1, 1)]);
+        // Edges.
+        assert_eq!(four((1, 0)), [(2, 0), (1, 1), (0, 0)]);
+        assert_eq!(eight((0, 1)), [(0, 0), (1, 0), (1, 1), (1, 2), (0, 2)]);
+        // Outside, including right at the edge of the coordinate space.
+        assert_eq!(four((-1, 0)), [(0, 0)]);
+        assert_eq!(eight((i64::MAX, i64::MIN)), []);
+
+        let line = Grid::new(1, 1, ());
+        assert_eq!(line.neighbors8((0, 0)).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "(3, 0) is outside a 3x2 grid")]
+    fn index_out_of_bounds() {
+        let grid = Grid::new(3, 2, 0);
+        let _ = grid[(3, 0)];
+    }
+
+    #[test]
+    #[should_panic(expected = "row 1 is ragged")]
+    fn ragged_rows() {
+        Grid::from_rows(vec![vec![1, 2], vec![3]]);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod collections;
+pub mod genlib;
+pub mod geom;
+pub mod grid;
+pub mod overlay;
+pub mod search;
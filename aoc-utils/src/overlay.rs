@@ -0,0 +1,106 @@
+//! Drawing search results back onto the map they came from, so that answers
+//! can be checked by eye.
+
+use crate::geom::Point;
+use crate::grid::Grid;
+
+/// Draws `grid` a character per cell and a line per row.
+pub fn render<T>(grid: &Grid<T>, mut cell: impl FnMut(&T) -> char) -> String {
+    let mut out = String::with_capacity((grid.width() + 1) * grid.height());
+    for row in grid.rows() {
+        out.extend(row.iter().map(&mut cell));
+        out.push('\n');
+    }
+    out
+}
+
+/// Like `render`, with `path` drawn over the top: `S` at its start, `E` at
+/// its end and `O` everywhere in between. Points outside the grid are left
+/// out.
+pub fn draw_path<T>(grid: &Grid<T>, path: &[Point], mut cell: impl FnMut(&T) -> char) -> String {
+    let mut chars = Grid::from_rows(
+        grid.rows()
+            .map(|row| row.iter().map(&mut cell).collect::<Vec<_>>()),
+    );
+    let mut mark = |p: Point, c: char| {
+        if let Some(cell) = chars.get_mut(p) {
+            *cell = c;
+        }
+    };
+    for &p in path {
+        mark(p, 'O');
+    }
+    if let (Some(&start), Some(&end)) = (path.first(), path.last()) {
+        mark(end, 'E');
+        mark(start, 'S');
+    }
+    render(&chars, |&c| c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{bfs_paths, dijkstra_paths};
+
+    const MAP: &str = "\
+#######
+#.....#
+#.###.#
+#...#.#
+#######
+";
+
+    #[test]
+    fn render_round_trips() {
+        let grid = Grid::parse(MAP, |c| c == '.');
+        assert_eq!(render(&grid, |&open| if open { '.' } else { '#' }), MAP);
+    }
+
+    #[test]
+    fn draws_shortest_path() {
+        let grid = Grid::parse(MAP, |c| c);
+        let open = |p: Point| grid[p] == '.';
+        let paths = bfs_paths((3, 3), |&p, push| {
+            grid.neighbors4(p).filter(|&q| open(q)).for_each(push)
+        });
+        let path = paths.path_to(&(5, 3)).unwrap();
+        assert_eq!(
+            draw_path(&grid, &path, |&c| c),
+            "\
+#######
+#OOOOO#
+#O###O#
+#OOS#E#
+#######
+"
+        );
+
+        // Make the top row expensive, and the short way round is the only
+        // way round.
+        let paths = dijkstra_paths((3, 3), |&p, push| {
+            for q in grid.neighbors4(p).filter(|&q| open(q)) {
+                push(q, if q.1 == 1 { 10 } else { 1 });
+            }
+        });
+        assert_eq!(paths.distances[&(5, 3)], 55);
+        let path = paths.path_to(&(1, 1)).unwrap();
+        assert_eq!(
+            draw_path(&grid, &path, |&c| c),
+            "\
+#######
+#E....#
+#O###.#
+#OOS#.#
+#######
+"
+        );
+    }
+
+    #[test]
+    fn path_edge_cases() {
+        let grid = Grid::parse("...\n", |c| c);
+        assert_eq!(draw_path(&grid, &[], |&c| c), "...\n");
+        assert_eq!(draw_path(&grid, &[(1, 0)], |&c| c), ".S.\n");
+        assert_eq!(draw_path(&grid, &[(-1, 0), (0, 0)], |&c| c), "E..\n");
+    }
+}
@@ -0,0 +1,719 @@
+use crate::collections::{HashMap, HashSet};
+use smallvec::SmallVec;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::hash::Hash;
+use std::iter;
+
+/// A neighbor list that stays on the stack for up to four entries (all a
+/// 4-connected grid ever has), for holding on to a node's neighbors without
+/// allocating.
+pub type Neighbors<N> = SmallVec<[N; 4]>;
+
+/// Breadth-first search from `start`, returning the distance to every
+/// reachable node.
+///
+/// Like every search here, it asks for a node's neighbors by calling
+/// `neighbors` with the node and a callback to push each one through, rather
+/// than having it return a collection, so expanding a node never allocates.
+pub fn bfs<N, F>(start: N, neighbors: F) -> HashMap<N, usize>
+where
+    N: Clone + Eq + Hash,
+    F: FnMut(&N, &mut dyn FnMut(N)),
+{
+    multi_source_bfs(iter::once((start, 0)), neighbors).distances
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paths<N>
+where
+    N: Eq + Hash,
+{
+    pub distances: HashMap<N, usize>,
+    /// The node each node was reached from on a shortest path. The start has
+    /// none.
+    pub predecessors: HashMap<N, N>,
+}
+
+impl<N> Paths<N>
+where
+    N: Clone + Eq + Hash,
+{
+    /// A shortest path from the start to `target`, both included.
+    pub fn path_to(&self, target: &N) -> Option<Vec<N>> {
+        if !self.distances.contains_key(target) {
+            return None;
+        }
+        let mut path = vec![target.clone()];
+        while let Some(previous) = self.predecessors.get(path.last().unwrap()) {
+            path.push(previous.clone());
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Like `bfs`, but remembers how each node was reached so that the shortest
+/// paths themselves can be recovered.
+pub fn bfs_paths<N, F>(start: N, mut neighbors: F) -> Paths<N>
+where
+    N: Clone + Eq + Hash,
+    F: FnMut(&N, &mut dyn FnMut(N)),
+{
+    let mut result = Paths {
+        distances: HashMap::default(),
+        predecessors: HashMap::default(),
+    };
+    let mut active = VecDeque::new();
+    result.distances.insert(start.clone(), 0);
+    active.push_back((start, 0));
+
+    while let Some((node, n)) = active.pop_front() {
+        neighbors(&node, &mut |next| {
+            if !result.distances.contains_key(&next) {
+                result.distances.insert(next.clone(), n + 1);
+                result.predecessors.insert(next.clone(), node.clone());
+                active.push_back((next, n + 1));
+            }
+        });
+    }
+
+    result
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiSource<N>
+where
+    N: Eq + Hash,
+{
+    pub distances: HashMap<N, usize>,
+    /// Index (in the order given) of the source each node is closest to,
+    /// counting each source's initial cost. Ties go to the source listed
+    /// first.
+    pub nearest: HashMap<N, usize>,
+}
+
+/// Breadth-first search outward from several sources at once, each starting
+/// at its own initial cost, e.g. a source that only becomes active after a
+/// delay.
+pub fn multi_source_bfs<N, F, S>(sources: S, mut neighbors: F) -> MultiSource<N>
+where
+    N: Clone + Eq + Hash,
+    F: FnMut(&N, &mut dyn FnMut(N)),
+    S: IntoIterator<Item = (N, usize)>,
+{
+    // Sources join the search once it reaches their cost.
+    let mut pending: Vec<(usize, N, usize)> = sources
+        .into_iter()
+        .enumerate()
+        .map(|(label, (node, cost))| (label, node, cost))
+        .collect();
+    pending.sort_by_key(|&(_, _, cost)| cost);
+    let mut pending = pending.into_iter().peekable();
+
+    let mut result = MultiSource {
+        distances: HashMap::default(),
+        nearest: HashMap::default(),
+    };
+
+    // Works a whole distance at a time, so that a node reached from several
+    // sources at that distance can go to the lowest-numbered one whichever
+    // order they arrive in.
+    let mut level: Vec<(N, usize)> = Vec::new();
+    let mut n = 0;
+    loop {
+        if level.is_empty() {
+            match pending.peek() {
+                Some(&(_, _, cost)) => n = n.max(cost),
+                None => break,
+            }
+        }
+
+        let mut next: HashMap<N, usize> = HashMap::default();
+        let mut claim = |node: N, label: usize| {
+            let best = next.entry(node).or_insert(label);
+            *best = (*best).min(label);
+        };
+        while let Some((label, node, _)) = pending.next_if(|&(_, _, cost)| cost <= n) {
+            if !result.distances.contains_key(&node) {
+                claim(node, label);
+            }
+        }
+        for (node, label) in &level {
+            neighbors(node, &mut |neighbor| {
+                if !result.distances.contains_key(&neighbor) {
+                    claim(neighbor, *label);
+                }
+            });
+        }
+
+        level.clear();
+        for (node, label) in next {
+            result.distances.insert(node.clone(), n);
+            result.nearest.insert(node.clone(), label);
+            level.push((node, label));
+        }
+        n += 1;
+    }
+
+    result
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eccentricity<N> {
+    pub distance: usize,
+    /// Every node at `distance`, in the order the search reached them.
+    pub farthest: Vec<N>,
+}
+
+/// The greatest BFS distance from `start` to any reachable node, along with
+/// all of the nodes that far away (e.g. how long oxygen takes to fill a maze
+/// from its source).
+pub fn eccentricity<N, F>(start: N, mut neighbors: F) -> Eccentricity<N>
+where
+    N: Clone + Eq + Hash,
+    F: FnMut(&N, &mut dyn FnMut(N)),
+{
+    let mut seen = HashSet::default();
+    let mut active = VecDeque::new();
+    seen.insert(start.clone());
+    active.push_back((start.clone(), 0));
+
+    let mut result = Eccentricity {
+        distance: 0,
+        farthest: vec![start],
+    };
+
+    while let Some((node, n)) = active.pop_front() {
+        neighbors(&node, &mut |next| {
+            if seen.insert(next.clone()) {
+                if n + 1 > result.distance {
+                    result.distance = n + 1;
+                    result.farthest.clear();
+                }
+                result.farthest.push(next.clone());
+                active.push_back((next, n + 1));
+            }
+        });
+    }
+
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContractionStats {
+    pub original_nodes: usize,
+    pub kept_nodes: usize,
+    pub edges: usize,
+}
+
+/// A graph with its corridors (chains of nodes with exactly two neighbors)
+/// collapsed into weighted edges between the nodes that remain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contracted<N>
+where
+    N: Eq + Hash,
+{
+    /// The shortest corridor from each kept node to each kept node it leads
+    /// to, as (other end, length).
+    pub edges: HashMap<N, Vec<(N, usize)>>,
+    /// The nodes strictly inside each corridor in `edges`, walking from the
+    /// first node of the key to the second.
+    pub corridors: HashMap<(N, N), Vec<N>>,
+    pub stats: ContractionStats,
+}
+
+impl<N> Contracted<N>
+where
+    N: Clone + Eq + Hash,
+{
+    /// Expands a path through kept nodes back into every node it passes
+    /// through in the original graph.
+    pub fn expand(&self, path: &[N]) -> Vec<N> {
+        let mut full: Vec<N> = path.iter().take(1).cloned().collect();
+        for step in path.windows(2) {
+            let key = (step[0].clone(), step[1].clone());
+            full.extend(self.corridors[&key].iter().cloned());
+            full.push(step[1].clone());
+        }
+        full
+    }
+}
+
+/// Contracts the part of an undirected graph reachable from `start`. Besides
+/// `start`, junctions and dead ends, nodes for which `keep` returns true
+/// (keys, doors, portals...) are kept even in the middle of a corridor.
+pub fn contract<N, F, K>(start: N, mut neighbors: F, mut keep: K) -> Contracted<N>
+where
+    N: Clone + Eq + Hash,
+    F: FnMut(&N, &mut dyn FnMut(N)),
+    K: FnMut(&N) -> bool,
+{
+    let mut adjacency: HashMap<N, Neighbors<N>> = HashMap::default();
+    let mut active = VecDeque::new();
+    active.push_back(start.clone());
+    while let Some(node) = active.pop_front() {
+        if adjacency.contains_key(&node) {
+            continue;
+        }
+        let mut next = Neighbors::new();
+        neighbors(&node, &mut |n| next.push(n));
+        active.extend(next.iter().cloned());
+        adjacency.insert(node, next);
+    }
+
+    let kept: HashSet<N> = adjacency
+        .iter()
+        .filter(|&(node, next)| *node == start || next.len() != 2 || keep(node))
+        .map(|(node, _)| node.clone())
+        .collect();
+
+    let mut result = Contracted {
+        edges: HashMap::default(),
+        corridors: HashMap::default(),
+        stats: ContractionStats {
+            original_nodes: adjacency.len(),
+            kept_nodes: kept.len(),
+            edges: 0,
+        },
+    };
+
+    for from in &kept {
+        let mut ends: Vec<(N, usize)> = Vec::new();
+        for first in &adjacency[from] {
+            let mut previous = from.clone();
+            let mut current = first.clone();
+            let mut inside = Vec::new();
+            while !kept.contains(&current) {
+                inside.push(current.clone());
+                let next = adjacency[&current]
+                    .iter()
+                    .find(|&n| *n != previous)
+                    .cloned()
+                    .unwrap_or_else(|| previous.clone());
+                previous = std::mem::replace(&mut current, next);
+            }
+            // A corridor looping straight back is no use to a search.
+            if current == *from {
+                continue;
+            }
+            let length = inside.len() + 1;
+            match ends.iter_mut().find(|(to, _)| *to == current) {
+                Some(end) if end.1 <= length => continue,
+                Some(end) => end.1 = length,
+                None => ends.push((current.clone(), length)),
+            }
+            result.corridors.insert((from.clone(), current), inside);
+        }
+        result.stats.edges += ends.len();
+        result.edges.insert(from.clone(), ends);
+    }
+
+    result
+}
+
+// Orders heap entries by cost alone, so nodes needn't be `Ord`.
+struct Entry<N> {
+    cost: Reverse<usize>,
+    node: N,
+}
+
+impl<N> PartialEq for Entry<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl<N> Eq for Entry<N> {}
+
+impl<N> PartialOrd for Entry<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> Ord for Entry<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost.cmp(&other.cost)
+    }
+}
+
+/// Dijkstra's algorithm from `start`, returning the cheapest cost to every
+/// reachable node. `neighbors` pushes each `(next, step cost)` through the
+/// callback it's given, as in `bfs`.
+pub fn dijkstra<N, F>(start: N, neighbors: F) -> HashMap<N, usize>
+where
+    N: Clone + Eq + Hash,
+    F: FnMut(&N, &mut dyn FnMut(N, usize)),
+{
+    dijkstra_paths(start, neighbors).distances
+}
+
+/// Like `dijkstra`, but also returns how each node was reached, as `bfs_paths`
+/// does. The `distances` are the cheapest costs.
+pub fn dijkstra_paths<N, F>(start: N, mut neighbors: F) -> Paths<N>
+where
+    N: Clone + Eq + Hash,
+    F: FnMut(&N, &mut dyn FnMut(N, usize)),
+{
+    let mut result = Paths {
+        distances: HashMap::default(),
+        predecessors: HashMap::default(),
+    };
+    let mut done = HashSet::default();
+    let mut active = BinaryHeap::new();
+    result.distances.insert(start.clone(), 0);
+    active.push(Entry {
+        cost: Reverse(0),
+        node: start,
+    });
+
+    while let Some(Entry {
+        cost: Reverse(cost),
+        node,
+    }) = active.pop()
+    {
+        if !done.insert(node.clone()) {
+            continue;
+        }
+        neighbors(&node, &mut |next, step| {
+            let next_cost = cost + step;
+            if result
+                .distances
+                .get(&next)
+                .is_none_or(|&known| next_cost < known)
+            {
+                result.distances.insert(next.clone(), next_cost);
+                result.predecessors.insert(next.clone(), node.clone());
+                active.push(Entry {
+                    cost: Reverse(next_cost),
+                    node: next,
+                });
+            }
+        });
+    }
+
+    result
+}
+
+/// Total weight of a minimum spanning tree over `nodes`. Visiting every node,
+/// starting from any of them, costs at least this much, so with the current
+/// position and the targets still to visit it makes an admissible A*
+/// heuristic for "collect everything" searches.
+pub fn mst_cost<N, D>(nodes: &[N], mut distance: D) -> usize
+where
+    D: FnMut(&N, &N) -> usize,
+{
+    if nodes.is_empty() {
+        return 0;
+    }
+    // Prim's algorithm; the graph is complete, so the O(n^2) form is best.
+    let mut in_tree = vec![false; nodes.len()];
+    let mut cheapest: Vec<usize> = nodes.iter().map(|n| distance(&nodes[0], n)).collect();
+    in_tree[0] = true;
+    let mut total = 0;
+    for _ in 1..nodes.len() {
+        let (next, cost) = cheapest
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| !in_tree[i])
+            .min_by_key(|&(_, &cost)| cost)
+            .map(|(i, &cost)| (i, cost))
+            .unwrap();
+        in_tree[next] = true;
+        total += cost;
+        for (i, node) in nodes.iter().enumerate() {
+            if !in_tree[i] {
+                cheapest[i] = cheapest[i].min(distance(&nodes[next], node));
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Cell = (i32, i32);
+
+    // Open cells of a map, 4-connected.
+    fn grid(map: &str) -> impl Fn(&Cell, &mut dyn FnMut(Cell)) {
+        let open: HashSet<Cell> = map
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|&(_, c)| c != '#')
+                    .map(move |(x, _)| (x as i32, y as i32))
+            })
+            .collect();
+        move |&(x, y), push| {
+            for &p in &[(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if open.contains(&p) {
+                    push(p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn contract_corridors() {
+        // A loop with a junction at (3, 1), from which a one-cell spur leads
+        // down to a dead end.
+        let map = "\
+#########
+#.......#
+#.#.###.#
+#.#####.#
+#.......#
+#########";
+        let start = (1, 1);
+        let contracted = contract(start, grid(map), |_| false);
+        assert_eq!(
+            contracted.stats,
+            ContractionStats {
+                original_nodes: 19,
+                kept_nodes: 3,
+                edges: 4,
+            }
+        );
+        // The long way round the loop loses to the short corridor.
+        assert_eq!(contracted.edges[&start], [((3, 1), 2)]);
+        let mut from_junction = contracted.edges[&(3, 1)].clone();
+        from_junction.sort();
+        assert_eq!(from_junction, [((1, 1), 2), ((3, 2), 1)]);
+        assert_eq!(
+            contracted.expand(&[(3, 2), (3, 1), start]),
+            [(3, 2), (3, 1), (2, 1), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn shortest_path() {
+        let map = "\
+#######
+#.....#
+#.###.#
+#.....#
+#######";
+        let paths = bfs_paths((1, 1), grid(map));
+        assert_eq!(paths.distances[&(3, 3)], 4);
+        assert_eq!(
+            paths.path_to(&(3, 3)),
+            Some(vec![(1, 1), (1, 2), (1, 3), (2, 3), (3, 3)])
+        );
+        assert_eq!(paths.path_to(&(1, 1)), Some(vec![(1, 1)]));
+        assert_eq!(paths.path_to(&(0, 0)), None);
+    }
+
+    // A path 0 - 1 - ... - (len - 1).
+    fn line(len: u32) -> impl Fn(&u32, &mut dyn FnMut(u32)) {
+        move |&n, push| {
+            for &m in &[n.wrapping_sub(1), n + 1] {
+                if m < len {
+                    push(m);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bfs_distances() {
+        let map = "\
+#######
+#...#.#
+#.#.#.#
+#.....#
+#######";
+        let distances = bfs((1, 1), grid(map));
+        assert_eq!(distances.len(), 12);
+        assert_eq!(distances[&(1, 1)], 0);
+        assert_eq!(distances[&(3, 1)], 2);
+        assert_eq!(distances[&(5, 1)], 8);
+        // A wall, and a cell outside the map.
+        assert_eq!(distances.get(&(2, 2)), None);
+        assert_eq!(distances.get(&(9, 9)), None);
+
+        let alone = bfs(7, |_: &u32, _| ());
+        assert_eq!(alone.into_iter().collect::<Vec<_>>(), [(7, 0)]);
+    }
+
+    #[test]
+    fn eccentricity_reports_every_farthest_node() {
+        // A plus shape: all four arm ends are one step from the middle.
+        let map = "\
+#####
+##.##
+#...#
+##.##
+#####";
+        let mut center = eccentricity((2, 2), grid(map));
+        assert_eq!(center.distance, 1);
+        center.farthest.sort();
+        assert_eq!(center.farthest, [(1, 2), (2, 1), (2, 3), (3, 2)]);
+
+        let end = eccentricity(0, line(5));
+        assert_eq!(end.distance, 4);
+        assert_eq!(end.farthest, [4]);
+        let middle = eccentricity(2, line(5));
+        assert_eq!(middle.distance, 2);
+        assert_eq!(middle.farthest, [0, 4]);
+    }
+
+    #[test]
+    fn eccentricity_edge_cases() {
+        // Only what's reachable counts: 3 and 4 are cut off from the rest.
+        let split = eccentricity(0, |&n: &u32, push| match n {
+            0 | 2 => push(1),
+            1 => {
+                push(0);
+                push(2);
+            }
+            3 => push(4),
+            _ => push(3),
+        });
+        assert_eq!(split.distance, 2);
+        assert_eq!(split.farthest, [2]);
+
+        let alone = eccentricity('a', |_: &char, _| ());
+        assert_eq!(alone.distance, 0);
+        assert_eq!(alone.farthest, ['a']);
+    }
+
+    #[test]
+    fn multi_source_distances() {
+        let spread = multi_source_bfs(vec![(0, 0), (6, 0)], line(7));
+        assert_eq!(spread.distances[&2], 2);
+        assert_eq!(spread.distances[&5], 1);
+        assert_eq!(spread.nearest[&2], 0);
+        assert_eq!(spread.nearest[&4], 1);
+        // 3 is as far from one end as the other.
+        assert_eq!(spread.nearest[&3], 0);
+
+        // A source that starts late still claims what it reaches first.
+        let spread = multi_source_bfs(vec![(0, 0), (6, 4)], line(7));
+        assert_eq!(spread.distances[&6], 4);
+        assert_eq!(spread.nearest[&6], 1);
+        assert_eq!(spread.distances[&5], 5);
+        assert_eq!(spread.nearest[&5], 0);
+    }
+
+    #[test]
+    fn multi_source_ties_go_to_first_listed() {
+        // 2 is two away from both: it's a source itself, but only from cost 2.
+        let spread = multi_source_bfs(vec![(2, 2), (0, 0)], line(5));
+        assert_eq!(spread.distances[&2], 2);
+        assert_eq!(spread.nearest[&2], 0);
+        assert_eq!(spread.nearest[&3], 0);
+        assert_eq!(spread.nearest[&1], 1);
+
+        let spread = multi_source_bfs(vec![(4, 0), (0, 0)], line(5));
+        assert_eq!(spread.nearest[&2], 0);
+        let spread = multi_source_bfs(vec![(0, 0), (4, 0)], line(5));
+        assert_eq!(spread.nearest[&2], 0);
+    }
+
+    #[test]
+    fn multi_source_edge_cases() {
+        let spread = multi_source_bfs(Vec::new(), line(3));
+        assert!(spread.distances.is_empty());
+
+        // Two sources on the same node; the cheaper one wins.
+        let spread = multi_source_bfs(vec![(1, 3), (1, 1)], line(3));
+        assert_eq!(spread.distances[&1], 1);
+        assert_eq!(spread.nearest[&1], 1);
+        assert_eq!(spread.distances[&0], 2);
+
+        // Nodes outside the sources' component are never reached.
+        let spread = multi_source_bfs(vec![(0, 0)], |&n: &u32, push| {
+            if n < 2 {
+                push(1 - n);
+            }
+        });
+        assert_eq!(spread.distances.len(), 2);
+        assert_eq!(spread.distances.get(&2), None);
+    }
+
+    #[test]
+    fn dijkstra_prefers_cheap_detours() {
+        // A direct edge that costs more than going the long way round.
+        let edges = [('a', 'b', 10), ('a', 'c', 1), ('c', 'b', 2), ('b', 'd', 1)];
+        let costs = dijkstra('a', |&node, push| {
+            for &(from, to, cost) in &edges {
+                if from == node {
+                    push(to, cost);
+                }
+            }
+        });
+        assert_eq!(costs[&'b'], 3);
+        assert_eq!(costs[&'d'], 4);
+        assert_eq!(costs.len(), 4);
+
+        let paths = dijkstra_paths('a', |&node, push| {
+            for &(from, to, cost) in &edges {
+                if from == node {
+                    push(to, cost);
+                }
+            }
+        });
+        assert_eq!(paths.distances, costs);
+        assert_eq!(paths.path_to(&'d'), Some(vec!['a', 'c', 'b', 'd']));
+        assert_eq!(paths.path_to(&'e'), None);
+    }
+
+    #[test]
+    fn mst_cost_is_admissible() {
+        use crate::geom::{manhattan, Point};
+        use itertools::Itertools;
+
+        let distance = |a: &Point, b: &Point| manhattan(*a, *b) as usize;
+        // A small deterministic spread of instances.
+        let mut seed = 12345u64;
+        let mut coordinate = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((seed >> 33) % 20) as i64
+        };
+        for size in 1..=6 {
+            for _ in 0..20 {
+                let points: Vec<Point> = (0..size).map(|_| (coordinate(), coordinate())).collect();
+                let bound = mst_cost(&points, distance);
+                let best_tour = (1..size)
+                    .permutations(size - 1)
+                    .map(|order| {
+                        let mut at = points[0];
+                        let mut cost = 0;
+                        for i in order {
+                            cost += distance(&at, &points[i]);
+                            at = points[i];
+                        }
+                        cost
+                    })
+                    .min()
+                    .unwrap();
+                assert!(
+                    bound <= best_tour,
+                    "{:?}: {} > {}",
+                    points,
+                    bound,
+                    best_tour
+                );
+            }
+        }
+        assert_eq!(mst_cost(&[(0, 0), (3, 0), (3, 4)], distance), 7);
+    }
+
+    #[test]
+    fn keep_marked_nodes() {
+        let map = "\
+#####
+#...#
+#####";
+        let contracted = contract((1, 1), grid(map), |&p| p == (2, 1));
+        assert_eq!(contracted.stats.kept_nodes, 3);
+        assert_eq!(contracted.edges[&(2, 1)].len(), 2);
+    }
+}
@@ -0,0 +1,164 @@
+//! VM throughput on whole programs, for judging interpreter changes, and the
+//! speed of parsing them. `cargo bench -p intcode` runs everything; pass a
+//! filter such as `boost` to run one group.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use intcode::asm::assemble;
+use intcode::parse::parse_program;
+use intcode::{run_collect, run_program, run_to_first_output, CostModel, Intcode};
+
+const BOOST: &str = include_str!("boost.intcode");
+const SIEVE: &str = include_str!("../samples/bench/sieve.intcode");
+const DEEP_RECURSION: &str = include_str!("../samples/bench/deep_recursion.intcode");
+
+/// How many instructions a run of `program` executes, so throughput comes out
+/// in instructions per second.
+fn instructions(program: &[i64], inputs: &[i64]) -> u64 {
+    let mut io = (inputs.iter().cloned(), Vec::new());
+    let mut machine = Intcode::new(program.to_vec(), &mut io);
+    machine.set_cost_model(CostModel::default());
+    machine.run().unwrap();
+    machine.cost()
+}
+
+/// The day 9 BOOST program: its self-test touches every opcode and mode once,
+/// while sensor boost mode is a long, call-heavy computation.
+fn boost(c: &mut Criterion) {
+    let program = parse_program(BOOST).unwrap();
+    let mut group = c.benchmark_group("boost");
+    group.bench_function("self_test", |b| {
+        b.iter(|| run_collect(black_box(&program), &[1]).unwrap())
+    });
+    group.sample_size(10);
+    group.bench_function("sensor_boost", |b| {
+        b.iter(|| run_to_first_output(black_box(&program), &[2]).unwrap())
+    });
+    group.finish();
+}
+
+/// Nothing but decode and arithmetic: counts down from its input, so the cost
+/// per iteration is three instructions with no IO.
+fn countdown(c: &mut Criterion) {
+    let program = assemble(
+        "
+            in n
+        loop:
+            add n, #-1, n
+            lt #0, n, t
+            jnz t, #loop
+            out n
+            halt
+        ",
+    )
+    .unwrap();
+    c.bench_function("countdown_100k", |b| {
+        b.iter(|| {
+            let mut io = (std::iter::once(100_000), Vec::new());
+            Intcode::new(program.clone(), &mut io).run().unwrap();
+            io.1
+        })
+    });
+}
+
+/// A program that writes one word at a far address and halts: what it costs
+/// is almost all in growing memory out to that address (the last is far enough
+/// to go in the sparse map instead).
+fn far_write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("far_write");
+    for &address in &[1_000, 60_000, 1_000_000_000] {
+        let program = assemble(&format!("add #1, #0, {}\nhalt", address)).unwrap();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(address),
+            &program,
+            |b, program| b.iter(|| run_program(black_box(program), &[]).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+/// The `samples/bench` programs, reported in instructions per second.
+fn samples(c: &mut Criterion) {
+    let cases: &[(&str, &str, &[i64])] = &[
+        ("sieve_100k", SIEVE, &[100_000]),
+        ("deep_recursion_3_6", DEEP_RECURSION, &[3, 6]),
+    ];
+    let mut group = c.benchmark_group("samples");
+    for &(name, source, inputs) in cases {
+        let program = parse_program(source).unwrap();
+        group.throughput(Throughput::Elements(instructions(&program, inputs)));
+        group.bench_function(name, |b| {
+            b.iter(|| run_collect(black_box(&program), inputs).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// `parse_program` against the `split(',')` and `str::parse` it replaced, on
+/// the day 9 program and on a synthetic one far bigger than any puzzle input.
+fn parse(c: &mut Criterion) {
+    let synthetic = (0..200_000i64)
+        .map(|i| match i % 4 {
+            0 => "1101".to_string(),
+            1 => (i * 7919 % 100_000).to_string(),
+            2 => (-i).to_string(),
+            _ => (i * 1_000_003 % 1_125_899_906_842_624).to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut group = c.benchmark_group("parse");
+    for &(name, text) in &[("boost", BOOST), ("synthetic_200k", &synthetic[..])] {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::new("scanner", name), text, |b, text| {
+            b.iter(|| parse_program(black_box(text)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("split", name), text, |b, text| {
+            b.iter(|| {
+                black_box(text)
+                    .trim()
+                    .split(',')
+                    .map(|word| word.trim().parse::<i64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// The same programs through the JIT (`--features jit`). One `Jit` is kept
+/// across iterations, as it would be for a program run over and over, so
+/// compile time only shows up in the first.
+#[cfg(feature = "jit")]
+fn jit(c: &mut Criterion) {
+    use intcode::io::QueueIo;
+    use intcode::jit::Jit;
+
+    let cases: &[(&str, &str, &[i64])] = &[
+        ("sieve_100k", SIEVE, &[100_000]),
+        ("deep_recursion_3_6", DEEP_RECURSION, &[3, 6]),
+    ];
+    let mut group = c.benchmark_group("jit");
+    for &(name, source, inputs) in cases {
+        let program = parse_program(source).unwrap();
+        group.throughput(Throughput::Elements(instructions(&program, inputs)));
+        let mut jit = Jit::new();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut io = QueueIo::new();
+                for &v in inputs {
+                    io.push_input(v);
+                }
+                let mut machine = Intcode::new(black_box(&program).clone(), io);
+                jit.run(&mut machine).unwrap();
+                machine.into_io().pop_output()
+            })
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "jit"))]
+criterion_group!(benches, boost, countdown, far_write, samples, parse);
+#[cfg(feature = "jit")]
+criterion_group!(benches, boost, countdown, far_write, samples, parse, jit);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+//! Runs arbitrary programs for a bounded number of instructions. Whatever the
+//! program does, the VM should stop with a `RunError` rather than panic.
+//!
+//! Run with `cargo +nightly fuzz run run` from `intcode/`.
+
+#![no_main]
+use intcode::{Intcode, IO};
+use libfuzzer_sys::fuzz_target;
+use std::convert::TryInto;
+
+const BUDGET: usize = 1_000;
+
+// A run stops once dense memory passes this many words (8 MB), rather than
+// letting a thousand far-apart writes add up to half a gigabyte.
+const MEMORY_CAP: usize = 1 << 20;
+
+// The most one instruction can grow dense memory by: 64K words past the end,
+// rounded up to a whole 1K page.
+const MAX_STEP_GROWTH: usize = (1 << 16) + (1 << 10);
+
+// Answers every input with the same value, so a run never blocks.
+struct Constant(i64);
+
+impl IO for Constant {
+    fn input(&mut self) -> i64 {
+        self.0
+    }
+
+    fn output(&mut self, _: i64) {}
+}
+
+fuzz_target!(|data: &[u8]| {
+    let program: Vec<i64> = data
+        .chunks_exact(8)
+        .map(|word| i64::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    let mut machine = Intcode::new(program, Constant(1));
+    for _ in 0..BUDGET {
+        let before = machine.ram().len();
+        let halted = match machine.step() {
+            Ok(step) => step.opcode == intcode::Opcode::Halt,
+            Err(_) => true,
+        };
+        let words = machine.ram().len();
+        assert!(
+            words <= before + MAX_STEP_GROWTH,
+            "one step grew memory from {} to {} words",
+            before,
+            words
+        );
+        if halted || words > MEMORY_CAP {
+            break;
+        }
+    }
+    // Anything further out is one map entry per write, at most one per step.
+    assert!(machine.snapshot().sparse.len() <= BUDGET);
+});
@@ -0,0 +1,338 @@
+//! A small assembler for writing Intcode by hand, in the syntax used by
+//! `samples/README.md`:
+//!
+//! ```text
+//!         in n            ; position operands are plain addresses or names
+//!         lt n, #2, t     ; '#' makes an operand immediate
+//!         jnz t, #done    ; labels can be used anywhere a number can
+//!         out rb+1        ; 'rb+N' / 'rb-N' are relative to the relative base
+//!     done:
+//!         halt
+//!     table: .data 1, 2, 3
+//! ```
+//!
+//! Names that aren't labels are variables: each gets a zero word of its own
+//! after the end of the program.
+
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    UnknownMnemonic {
+        line: usize,
+        mnemonic: String,
+    },
+    OperandCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    BadOperand {
+        line: usize,
+        operand: String,
+    },
+    DuplicateLabel {
+        line: usize,
+        label: String,
+    },
+    ImmediateDestination {
+        line: usize,
+    },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {}: unknown mnemonic {:?}", line, mnemonic)
+            }
+            AsmError::OperandCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} operands, found {}",
+                line, expected, found
+            ),
+            AsmError::BadOperand { line, operand } => {
+                write!(f, "line {}: can't parse operand {:?}", line, operand)
+            }
+            AsmError::DuplicateLabel { line, label } => {
+                write!(f, "line {}: label {:?} is already defined", line, label)
+            }
+            AsmError::ImmediateDestination { line } => {
+                write!(f, "line {}: destination can't be immediate", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(i64),
+    Name(String),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Position(Value),
+    Immediate(Value),
+    Relative(i64),
+}
+
+impl Operand {
+    fn mode(&self) -> i64 {
+        match self {
+            Operand::Position(_) => 0,
+            Operand::Immediate(_) => 1,
+            Operand::Relative(_) => 2,
+        }
+    }
+}
+
+// (mnemonic, opcode, operand count, whether the last operand is written)
+const MNEMONICS: &[(&str, i64, usize, bool)] = &[
+    ("add", 1, 3, true),
+    ("mul", 2, 3, true),
+    ("in", 3, 1, true),
+    ("out", 4, 1, false),
+    ("jnz", 5, 2, false),
+    ("jz", 6, 2, false),
+    ("lt", 7, 3, true),
+    ("eq", 8, 3, true),
+    ("arb", 9, 1, false),
+    ("halt", 99, 0, false),
+];
+
+enum Item {
+    Instruction { opcode: i64, operands: Vec<Operand> },
+    Data(Vec<Value>),
+}
+
+/// Assembles `source` into a program ready for `Intcode::new`.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut items = Vec::new();
+    let mut labels = HashMap::new();
+    let mut address = 0;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut text = line.split(';').next().unwrap_or("").trim();
+
+        while let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if !is_name(label) {
+                return Err(AsmError::BadOperand {
+                    line: line_number,
+                    operand: label.to_string(),
+                });
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(AsmError::DuplicateLabel {
+                    line: line_number,
+                    label: label.to_string(),
+                });
+            }
+            text = text[colon + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = match text.find(char::is_whitespace) {
+            Some(split) => (&text[..split], text[split..].trim()),
+            None => (text, ""),
+        };
+        let args: Vec<&str> = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',').map(str::trim).collect()
+        };
+
+        if mnemonic == ".data" {
+            let values = args
+                .iter()
+                .map(|arg| parse_value(arg, line_number))
+                .collect::<Result<Vec<_>, _>>()?;
+            address += values.len();
+            items.push(Item::Data(values));
+            continue;
+        }
+
+        let &(_, opcode, count, writes) = MNEMONICS
+            .iter()
+            .find(|(name, ..)| *name == mnemonic)
+            .ok_or_else(|| AsmError::UnknownMnemonic {
+                line: line_number,
+                mnemonic: mnemonic.to_string(),
+            })?;
+        if args.len() != count {
+            return Err(AsmError::OperandCount {
+                line: line_number,
+                expected: count,
+                found: args.len(),
+            });
+        }
+        let operands = args
+            .iter()
+            .map(|arg| parse_operand(arg, line_number))
+            .collect::<Result<Vec<_>, _>>()?;
+        if writes {
+            if let Some(Operand::Immediate(_)) = operands.last() {
+                return Err(AsmError::ImmediateDestination { line: line_number });
+            }
+        }
+        address += 1 + count;
+        items.push(Item::Instruction { opcode, operands });
+    }
+
+    let mut variables = Vec::new();
+    let mut resolve = |value: &Value| match value {
+        Value::Number(n) => *n,
+        Value::Name(name) => match labels.get(name) {
+            Some(&address) => address as i64,
+            None => {
+                let slot = match variables.iter().position(|v| v == name) {
+                    Some(slot) => slot,
+                    None => {
+                        variables.push(name.clone());
+                        variables.len() - 1
+                    }
+                };
+                (address + slot) as i64
+            }
+        },
+    };
+
+    let mut program = Vec::with_capacity(address);
+    for item in &items {
+        match item {
+            Item::Instruction { opcode, operands } => {
+                let modes = operands
+                    .iter()
+                    .rev()
+                    .fold(0, |modes, operand| modes * 10 + operand.mode());
+                program.push(modes * 100 + opcode);
+                for operand in operands {
+                    program.push(match operand {
+                        Operand::Position(value) | Operand::Immediate(value) => resolve(value),
+                        Operand::Relative(offset) => *offset,
+                    });
+                }
+            }
+            Item::Data(values) => program.extend(values.iter().map(&mut resolve)),
+        }
+    }
+    program.resize(address + variables.len(), 0);
+    Ok(program)
+}
+
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn parse_value(s: &str, line: usize) -> Result<Value, AsmError> {
+    if is_name(s) {
+        Ok(Value::Name(s.to_string()))
+    } else {
+        s.parse()
+            .map(Value::Number)
+            .map_err(|_| AsmError::BadOperand {
+                line,
+                operand: s.to_string(),
+            })
+    }
+}
+
+fn parse_operand(s: &str, line: usize) -> Result<Operand, AsmError> {
+    if let Some(value) = s.strip_prefix('#') {
+        return parse_value(value, line).map(Operand::Immediate);
+    }
+    if let Some(offset) = s.strip_prefix("rb") {
+        let offset = offset.replace(' ', "");
+        if offset.starts_with('+') || offset.starts_with('-') {
+            let offset = offset.strip_prefix('+').unwrap_or(&offset);
+            return offset
+                .parse()
+                .map(Operand::Relative)
+                .map_err(|_| AsmError::BadOperand {
+                    line,
+                    operand: s.to_string(),
+                });
+        }
+    }
+    parse_value(s, line).map(Operand::Position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Intcode;
+
+    fn run(source: &str, inputs: &[i64]) -> Vec<i64> {
+        let mut io = (inputs.iter().cloned(), Vec::new());
+        Intcode::new(assemble(source).unwrap(), &mut io)
+            .run()
+            .unwrap();
+        io.1
+    }
+
+    #[test]
+    fn encodes_modes_and_variables() {
+        let program = assemble("in a\nadd a, #5, rb-2\nhalt").unwrap();
+        assert_eq!(program, [3, 7, 21001, 7, 5, -2, 99, 0]);
+    }
+
+    #[test]
+    fn labels_and_data() {
+        let source = "
+                out table       ; position mode reads the word at the label
+                out #table
+                jnz #1, #end
+                out #-1
+            end: halt
+            table: .data 42, end
+        ";
+        assert_eq!(run(source, &[]), [42, 10]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            assemble("nop"),
+            Err(AsmError::UnknownMnemonic {
+                line: 1,
+                mnemonic: "nop".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("halt\nadd a, b"),
+            Err(AsmError::OperandCount {
+                line: 2,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            assemble("in #3"),
+            Err(AsmError::ImmediateDestination { line: 1 })
+        );
+        assert_eq!(
+            assemble("x: halt\nx: halt"),
+            Err(AsmError::DuplicateLabel {
+                line: 2,
+                label: "x".to_string()
+            })
+        );
+    }
+}
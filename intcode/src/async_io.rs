@@ -0,0 +1,160 @@
+//! Machines hosted in an async program, whose input and output await rather
+//! than block a worker thread. Enabled by the `tokio` feature.
+//!
+//! Only IO yields to the executor: a long stretch of pure computation between
+//! inputs runs to completion in one poll, just as it would on a thread.
+
+use crate::{Intcode, RunError, StopReason, IO};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+pub type IoFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The async counterpart of `IO`.
+pub trait AsyncIO {
+    fn input(&mut self) -> IoFuture<'_, i64>;
+    fn output(&mut self, v: i64) -> IoFuture<'_, ()>;
+}
+
+/// Reads input from and sends output to tokio channels, the async version of
+/// `io::ChannelIo`.
+#[derive(Debug)]
+pub struct TokioChannelIo {
+    input: Receiver<i64>,
+    output: Sender<i64>,
+}
+
+impl TokioChannelIo {
+    pub fn new(input: Receiver<i64>, output: Sender<i64>) -> TokioChannelIo {
+        TokioChannelIo { input, output }
+    }
+}
+
+impl AsyncIO for TokioChannelIo {
+    fn input(&mut self) -> IoFuture<'_, i64> {
+        Box::pin(async move {
+            self.input
+                .recv()
+                .await
+                .expect("input channel closed while the program wanted input")
+        })
+    }
+
+    fn output(&mut self, v: i64) -> IoFuture<'_, ()> {
+        Box::pin(async move {
+            // As with ChannelIo, a receiver that's gone away no longer cares.
+            let _ = self.output.send(v).await;
+        })
+    }
+}
+
+// The machine's own IO: holds the one input awaited on its behalf, and drops
+// outputs, which `run` picks up from the stop reason instead.
+#[derive(Debug, Default)]
+struct Slot(Option<i64>);
+
+impl IO for Slot {
+    fn input(&mut self) -> i64 {
+        self.0.take().expect("input requested outside run")
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        self.0.take()
+    }
+
+    fn output(&mut self, _: i64) {}
+}
+
+/// An `Intcode` machine driven by an `AsyncIO`.
+pub struct AsyncIntcode<A> {
+    machine: Intcode<Slot>,
+    io: A,
+}
+
+impl<A> AsyncIntcode<A>
+where
+    A: AsyncIO,
+{
+    pub fn new(ram: Vec<i64>, io: A) -> AsyncIntcode<A> {
+        AsyncIntcode {
+            machine: Intcode::new(ram, Slot::default()),
+            io,
+        }
+    }
+
+    /// See `Intcode::shared`.
+    pub fn shared(program: Arc<[i64]>, io: A) -> AsyncIntcode<A> {
+        AsyncIntcode {
+            machine: Intcode::shared(program, Slot::default()),
+            io,
+        }
+    }
+
+    /// Runs until the machine halts, awaiting the IO for every input and
+    /// output.
+    pub async fn run(&mut self) -> Result<(), RunError> {
+        loop {
+            match self.machine.run_until_event()? {
+                StopReason::Halted => return Ok(()),
+                StopReason::NeedsInput => self.machine.io_mut().0 = Some(self.io.input().await),
+                StopReason::Output(v) => self.io.output(v).await,
+                // There's no way to set up either of these from here.
+                StopReason::Breakpoint(_) | StopReason::DefaultInput => (),
+            }
+        }
+    }
+
+    pub fn ram(&self) -> &[i64] {
+        self.machine.ram()
+    }
+
+    pub fn io(&self) -> &A {
+        &self.io
+    }
+
+    pub fn io_mut(&mut self) -> &mut A {
+        &mut self.io
+    }
+
+    pub fn into_io(self) -> A {
+        self.io
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use tokio::sync::mpsc::channel;
+
+    #[tokio::test]
+    async fn doubles_over_channels() {
+        let program = assemble(
+            "
+            loop:
+                in n
+                jz n, #done
+                mul n, #2, n
+                out n
+                jnz #1, #loop
+            done:
+                halt
+            ",
+        )
+        .unwrap();
+        let (to_machine, input) = channel(1);
+        let (output, mut from_machine) = channel(1);
+        let mut machine = AsyncIntcode::new(program, TokioChannelIo::new(input, output));
+        let running = tokio::spawn(async move { machine.run().await });
+
+        for n in 1..=3 {
+            to_machine.send(n).await.unwrap();
+            assert_eq!(from_machine.recv().await, Some(2 * n));
+        }
+        to_machine.send(0).await.unwrap();
+        running.await.unwrap().unwrap();
+        assert_eq!(from_machine.recv().await, None);
+    }
+}
@@ -0,0 +1,15 @@
+use intcode::io::{BatchedOutput, InteractiveIo};
+use intcode::parse::parse_program;
+use intcode::Intcode;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or("usage: console <program file>")?;
+    let program = parse_program(&std::fs::read_to_string(path)?)?;
+
+    let mut io = BatchedOutput::new(InteractiveIo::new(), std::io::stdout(), 1024);
+    Intcode::new(program, &mut io).run()?;
+    io.flush()?;
+    Ok(())
+}
@@ -0,0 +1,290 @@
+//! Several machines sharing one thread, each with its own input queue, and
+//! with outputs optionally wired into another machine's input, as in day 7's
+//! amplifier feedback loop.
+
+use crate::io::QueueIo;
+use crate::{Intcode, RunError, StopReason};
+use std::fmt;
+
+/// A `RunError` from one machine in a `Cluster`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineError {
+    pub machine: usize,
+    pub error: RunError,
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "machine {}: {}", self.machine, self.error)
+    }
+}
+
+impl std::error::Error for MachineError {}
+
+struct Node {
+    machine: Intcode<QueueIo>,
+    route: Option<usize>,
+    blocked: bool,
+    halted: bool,
+    last_output: Option<i64>,
+}
+
+/// Machines scheduled round-robin with `run_until_event`: each gets to run
+/// until it halts or waits on an empty input queue, then the next gets a turn.
+/// Machines are numbered in the order they were added.
+#[derive(Default)]
+pub struct Cluster {
+    nodes: Vec<Node>,
+}
+
+impl Cluster {
+    pub fn new() -> Cluster {
+        Cluster::default()
+    }
+
+    /// Adds a machine, returning its number. Anything already queued in its
+    /// IO is kept.
+    pub fn add(&mut self, machine: Intcode<QueueIo>) -> usize {
+        self.nodes.push(Node {
+            machine,
+            route: None,
+            blocked: false,
+            halted: false,
+            last_output: None,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Sends everything `from` outputs from now on to `to`'s input queue,
+    /// instead of leaving it for `pop_output`.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(to < self.nodes.len(), "no machine {}", to);
+        self.nodes[from].route = Some(to);
+    }
+
+    pub fn push_input(&mut self, machine: usize, v: i64) {
+        self.nodes[machine].machine.io_mut().push_input(v);
+    }
+
+    /// The oldest output from `machine` that hasn't been routed elsewhere or
+    /// popped already.
+    pub fn pop_output(&mut self, machine: usize) -> Option<i64> {
+        self.nodes[machine].machine.io_mut().pop_output()
+    }
+
+    /// The most recent value `machine` output, whether or not it was routed.
+    pub fn last_output(&self, machine: usize) -> Option<i64> {
+        self.nodes[machine].last_output
+    }
+
+    pub fn machine(&self, machine: usize) -> &Intcode<QueueIo> {
+        &self.nodes[machine].machine
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn is_halted(&self, machine: usize) -> bool {
+        self.nodes[machine].halted
+    }
+
+    /// Whether `machine` last stopped for input (or was given its input
+    /// default) and nothing has been queued for it since.
+    pub fn is_waiting(&self, machine: usize) -> bool {
+        let node = &self.nodes[machine];
+        !node.halted && node.blocked && !node.machine.io().has_input()
+    }
+
+    /// Whether every machine that hasn't halted is waiting on an empty input
+    /// queue, which is always the case once `run` returns. If any are waiting
+    /// at all, nothing more happens until somebody pushes input: a NAT's cue
+    /// to act, or a deadlock.
+    pub fn is_idle(&self) -> bool {
+        (0..self.nodes.len()).all(|machine| self.is_halted(machine) || self.is_waiting(machine))
+    }
+
+    /// Runs machines in turn until none of them can make progress: each has
+    /// either halted or is waiting on an empty input queue. A machine with an
+    /// input default counts as waiting once it has been given the default.
+    /// Breakpoints are carried on past. Push more input and call again to
+    /// resume.
+    pub fn run(&mut self) -> Result<(), MachineError> {
+        loop {
+            let mut progressed = false;
+            for index in 0..self.nodes.len() {
+                if self.runnable(index) {
+                    progressed = true;
+                    self.run_machine(index).map_err(|error| MachineError {
+                        machine: index,
+                        error,
+                    })?;
+                }
+            }
+            if !progressed {
+                return Ok(());
+            }
+        }
+    }
+
+    fn runnable(&self, index: usize) -> bool {
+        let node = &self.nodes[index];
+        !node.halted && (!node.blocked || node.machine.io().has_input())
+    }
+
+    // One turn for one machine.
+    fn run_machine(&mut self, index: usize) -> Result<(), RunError> {
+        loop {
+            let node = &mut self.nodes[index];
+            node.blocked = false;
+            match node.machine.run_until_event()? {
+                StopReason::Output(v) => {
+                    node.last_output = Some(v);
+                    if let Some(to) = node.route {
+                        node.machine.io_mut().pop_last_output();
+                        self.nodes[to].machine.io_mut().push_input(v);
+                    }
+                }
+                StopReason::NeedsInput | StopReason::DefaultInput => {
+                    node.blocked = true;
+                    return Ok(());
+                }
+                StopReason::Halted => {
+                    node.halted = true;
+                    return Ok(());
+                }
+                StopReason::Breakpoint(_) => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn machine(source: &str) -> Intcode<QueueIo> {
+        Intcode::new(assemble(source).unwrap(), QueueIo::new())
+    }
+
+    #[test]
+    fn ping_pong() {
+        // Each machine adds one to whatever it's sent and passes it on, until
+        // the count passes ten.
+        let source = "
+            loop:
+                in n
+                add n, #1, n
+                out n
+                lt n, #10, t
+                jnz t, #loop
+                halt
+        ";
+        let mut cluster = Cluster::new();
+        let a = cluster.add(machine(source));
+        let b = cluster.add(machine(source));
+        cluster.connect(a, b);
+        cluster.connect(b, a);
+        cluster.push_input(a, 0);
+        cluster.run().unwrap();
+
+        assert!(cluster.is_halted(a) && cluster.is_halted(b));
+        assert_eq!(cluster.last_output(a), Some(11));
+        assert_eq!(cluster.last_output(b), Some(10));
+        assert_eq!(cluster.pop_output(a), None);
+    }
+
+    #[test]
+    fn resumes_with_more_input() {
+        let mut cluster = Cluster::new();
+        let echo = cluster.add(machine("loop: in n\nout n\njnz #1, #loop"));
+        cluster.run().unwrap();
+        assert_eq!(cluster.pop_output(echo), None);
+
+        cluster.push_input(echo, 7);
+        cluster.push_input(echo, 8);
+        cluster.run().unwrap();
+        assert_eq!(cluster.pop_output(echo), Some(7));
+        assert_eq!(cluster.pop_output(echo), Some(8));
+        assert!(!cluster.is_halted(echo));
+    }
+
+    #[test]
+    fn connect_keeps_earlier_outputs() {
+        let mut cluster = Cluster::new();
+        let echo = cluster.add(machine("loop: in n\nout n\njnz #1, #loop"));
+        let sink = cluster.add(machine("loop: in n\nout n\njnz #1, #loop"));
+        cluster.push_input(echo, 1);
+        cluster.push_input(echo, 2);
+        cluster.run().unwrap();
+        assert_eq!(cluster.pop_output(sink), None);
+
+        // Only what's output after connecting is routed.
+        cluster.connect(echo, sink);
+        cluster.push_input(echo, 3);
+        cluster.run().unwrap();
+        assert_eq!(cluster.pop_output(echo), Some(1));
+        assert_eq!(cluster.pop_output(echo), Some(2));
+        assert_eq!(cluster.pop_output(echo), None);
+        assert_eq!(cluster.pop_output(sink), Some(3));
+        assert_eq!(cluster.pop_output(sink), None);
+    }
+
+    #[test]
+    fn idle_detection() {
+        let mut cluster = Cluster::new();
+        let a = cluster.add(machine("in x\nout x\nhalt"));
+        let b = cluster.add(machine("in x\nout x\nhalt"));
+        cluster.connect(a, b);
+        cluster.connect(b, a);
+        assert!(!cluster.is_idle());
+
+        // Each is waiting for the other to go first.
+        cluster.run().unwrap();
+        assert!(cluster.is_idle());
+        assert!(cluster.is_waiting(a) && cluster.is_waiting(b));
+
+        cluster.push_input(b, 3);
+        assert!(!cluster.is_idle() && !cluster.is_waiting(b));
+        cluster.run().unwrap();
+        assert!(cluster.is_idle());
+        assert!(cluster.is_halted(a) && cluster.is_halted(b));
+        assert!(!cluster.is_waiting(a));
+    }
+
+    #[test]
+    fn input_default_counts_as_waiting() {
+        let mut polling = machine("loop: in x\njz x, #loop\nout x\nhalt");
+        polling.set_input_default(Some(0));
+        let mut cluster = Cluster::new();
+        let polling = cluster.add(polling);
+        cluster.run().unwrap();
+        assert!(cluster.is_waiting(polling));
+
+        cluster.push_input(polling, 9);
+        cluster.run().unwrap();
+        assert_eq!(cluster.pop_output(polling), Some(9));
+    }
+
+    #[test]
+    fn reports_which_machine_failed() {
+        let mut cluster = Cluster::new();
+        cluster.add(machine("halt"));
+        let broken = cluster.add(Intcode::new(vec![42], QueueIo::new()));
+        assert_eq!(
+            cluster.run(),
+            Err(MachineError {
+                machine: broken,
+                error: RunError::InvalidOpcode {
+                    pc: 0,
+                    instruction: 42
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Which parts of a program have actually run, e.g. to check that a puzzle
+//! program's self-test sections were all exercised, and where it rewrote
+//! code that had already run.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+
+/// A write into a word that had already been executed, as seen by
+/// `Intcode::track_coverage`; nothing is recorded without it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SelfModification {
+    /// The instruction doing the writing.
+    pub pc: usize,
+    pub address: usize,
+}
+
+/// The words executed since `Intcode::track_coverage`, counting each
+/// instruction's operands along with its opcode word.
+///
+/// Words within the original program are kept in a bitmap; anything run
+/// beyond it (e.g. code written to a far address and jumped to) is kept by
+/// address, so such a jump doesn't allocate a bitmap all the way out there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    executed: Vec<bool>,
+    beyond: BTreeSet<usize>,
+    program_len: usize,
+    modifications: BTreeSet<SelfModification>,
+}
+
+impl Coverage {
+    pub(crate) fn new(program_len: usize) -> Coverage {
+        Coverage {
+            executed: vec![false; program_len],
+            beyond: BTreeSet::new(),
+            program_len,
+            modifications: BTreeSet::new(),
+        }
+    }
+
+    pub(crate) fn written(&mut self, pc: usize, address: usize) {
+        if self.is_executed(address) {
+            self.modifications.insert(SelfModification { pc, address });
+        }
+    }
+
+    pub(crate) fn mark(&mut self, start: usize, len: usize) {
+        for address in start..start + len {
+            match self.executed.get_mut(address) {
+                Some(executed) => *executed = true,
+                None => {
+                    self.beyond.insert(address);
+                }
+            }
+        }
+    }
+
+    pub fn is_executed(&self, address: usize) -> bool {
+        match self.executed.get(address) {
+            Some(&executed) => executed,
+            None => self.beyond.contains(&address),
+        }
+    }
+
+    /// How many words have been executed, including any outside the
+    /// program.
+    pub fn executed_count(&self) -> usize {
+        self.executed.iter().filter(|&&executed| executed).count() + self.beyond.len()
+    }
+
+    /// Every distinct instruction/target pair that rewrote code executed
+    /// since tracking started, ordered by pc.
+    pub fn self_modifications(&self) -> impl Iterator<Item = SelfModification> + '_ {
+        self.modifications.iter().cloned()
+    }
+
+    /// The length of memory when tracking started.
+    pub fn program_len(&self) -> usize {
+        self.program_len
+    }
+
+    /// Runs of the program that have never been executed, in address order.
+    /// Data lives here too, so not everything listed is dead code.
+    pub fn unexecuted(&self) -> Vec<Range<usize>> {
+        let mut regions = Vec::new();
+        let mut start = None;
+        for address in 0..self.program_len {
+            match (self.is_executed(address), start) {
+                (false, None) => start = Some(address),
+                (true, Some(from)) => {
+                    regions.push(from..address);
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(from) = start {
+            regions.push(from..self.program_len);
+        }
+        regions
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let covered = (0..self.program_len)
+            .filter(|&address| self.is_executed(address))
+            .count();
+        writeln!(f, "{} of {} words executed", covered, self.program_len)?;
+        for region in self.unexecuted() {
+            writeln!(f, "unexecuted: {}..{}", region.start, region.end)?;
+        }
+        for modification in self.self_modifications() {
+            writeln!(
+                f,
+                "self-modifying: pc {} wrote {}",
+                modification.pc, modification.address
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::Intcode;
+
+    #[test]
+    fn untaken_branch() {
+        let program = assemble(
+            "
+                in n
+                jz n, #zero
+                out #1
+                halt
+            zero:
+                out #0
+                halt
+            ",
+        )
+        .unwrap();
+        let mut io = (vec![0].into_iter(), Vec::new());
+        let mut machine = Intcode::new(program, &mut io);
+        machine.track_coverage();
+        machine.run().unwrap();
+
+        let coverage = machine.coverage().unwrap();
+        assert_eq!(coverage.executed_count(), 8);
+        assert!(coverage.is_executed(8));
+        assert!(!coverage.is_executed(7));
+        // The other branch, then the variable `n`.
+        assert_eq!(coverage.unexecuted(), [5..8, 11..12]);
+        assert_eq!(
+            coverage.to_string(),
+            "8 of 12 words executed\nunexecuted: 5..8\nunexecuted: 11..12\n"
+        );
+    }
+
+    #[test]
+    fn self_modification() {
+        let program = assemble(
+            "
+                out #1
+                add #5, #0, 1   ; rewrites the operand of the out above
+                add c, #1, c
+                lt c, #2, t
+                jnz t, #0
+                halt
+            ",
+        )
+        .unwrap();
+        let mut io = (std::iter::empty(), Vec::new());
+        let mut machine = Intcode::new(program, &mut io);
+        machine.track_coverage();
+        machine.run().unwrap();
+
+        let coverage = machine.coverage().unwrap();
+        assert_eq!(
+            coverage.self_modifications().collect::<Vec<_>>(),
+            [SelfModification { pc: 2, address: 1 }]
+        );
+        assert!(coverage
+            .to_string()
+            .ends_with("self-modifying: pc 2 wrote 1\n"));
+    }
+
+    #[test]
+    fn far_code() {
+        // Writes `out #7; halt` a long way out and jumps to it.
+        let program = assemble(
+            "
+                add #104, #0, 1000000000
+                add #7, #0, 1000000001
+                add #99, #0, 1000000002
+                jz #0, #1000000000
+            ",
+        )
+        .unwrap();
+        let mut io = (std::iter::empty(), Vec::new());
+        let mut machine = Intcode::new(program, &mut io);
+        machine.track_coverage();
+        machine.run().unwrap();
+
+        let coverage = machine.coverage().unwrap();
+        assert_eq!(coverage.executed.len(), coverage.program_len());
+        assert_eq!(coverage.executed_count(), 15 + 3);
+        assert!(coverage.is_executed(1_000_000_002));
+        assert!(!coverage.is_executed(1_000_000_003));
+        assert_eq!(coverage.unexecuted(), []);
+        drop(machine);
+        assert_eq!(io.1, [7]);
+    }
+}
@@ -0,0 +1,726 @@
+//! Ready-made `IO` implementations.
+
+use crate::IO;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+
+/// What `InteractiveIo` does when stdin runs dry while the program still wants
+/// input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EofPolicy {
+    Panic,
+    Return(i64),
+}
+
+/// Prompts for each input on stdin, retrying until it gets an integer, and
+/// prints each output on its own line.
+#[derive(Debug, Clone)]
+pub struct InteractiveIo {
+    prompt: String,
+    retry_prompt: String,
+    eof: EofPolicy,
+}
+
+impl InteractiveIo {
+    pub fn new() -> InteractiveIo {
+        InteractiveIo {
+            prompt: "Input required.".to_string(),
+            retry_prompt: "Invalid integer, try again.".to_string(),
+            eof: EofPolicy::Panic,
+        }
+    }
+
+    pub fn prompt(mut self, prompt: &str) -> InteractiveIo {
+        self.prompt = prompt.to_string();
+        self
+    }
+
+    pub fn retry_prompt(mut self, prompt: &str) -> InteractiveIo {
+        self.retry_prompt = prompt.to_string();
+        self
+    }
+
+    pub fn on_eof(mut self, policy: EofPolicy) -> InteractiveIo {
+        self.eof = policy;
+        self
+    }
+}
+
+impl Default for InteractiveIo {
+    fn default() -> Self {
+        InteractiveIo::new()
+    }
+}
+
+impl IO for InteractiveIo {
+    fn input(&mut self) -> i64 {
+        println!("{}", self.prompt);
+        let stdin = std::io::stdin();
+        loop {
+            let mut input = String::new();
+            let read = stdin.lock().read_line(&mut input).expect("input required");
+            if read == 0 {
+                match self.eof {
+                    EofPolicy::Panic => panic!("stdin closed while the program wanted input"),
+                    EofPolicy::Return(v) => return v,
+                }
+            }
+            if let Ok(n) = input.trim().parse::<i64>() {
+                return n;
+            }
+            println!("{}", self.retry_prompt);
+        }
+    }
+
+    fn output(&mut self, v: i64) {
+        println!("{}", v);
+    }
+}
+
+/// Takes input from `inner`, but collects outputs (one per line) and writes
+/// them to `sink` in batches rather than one write per value. Pending output
+/// is flushed before every input, so prompts and results stay in order, and
+/// when the wrapper is dropped.
+pub struct BatchedOutput<T, W>
+where
+    T: IO,
+    W: Write,
+{
+    inner: T,
+    sink: W,
+    batch: usize,
+    pending: usize,
+    buffer: Vec<u8>,
+    error: Option<io::Error>,
+}
+
+impl<T, W> BatchedOutput<T, W>
+where
+    T: IO,
+    W: Write,
+{
+    pub fn new(inner: T, sink: W, batch: usize) -> BatchedOutput<T, W> {
+        BatchedOutput {
+            inner,
+            sink,
+            batch: batch.max(1),
+            pending: 0,
+            buffer: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Writes out anything still pending. Also reports the first error hit by
+    /// an automatic flush, since `IO::output` has no way to return one.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        if !self.buffer.is_empty() {
+            self.sink.write_all(&self.buffer)?;
+            self.buffer.clear();
+            self.pending = 0;
+        }
+        self.sink.flush()
+    }
+
+    fn flush_quietly(&mut self) {
+        if self.error.is_none() {
+            if let Err(error) = self.flush() {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+impl<T, W> IO for BatchedOutput<T, W>
+where
+    T: IO,
+    W: Write,
+{
+    fn input(&mut self) -> i64 {
+        self.flush_quietly();
+        self.inner.input()
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        self.flush_quietly();
+        self.inner.try_input()
+    }
+
+    fn output(&mut self, v: i64) {
+        writeln!(self.buffer, "{}", v).expect("writing to a Vec can't fail");
+        self.pending += 1;
+        if self.pending >= self.batch {
+            self.flush_quietly();
+        }
+    }
+}
+
+impl<T, W> Drop for BatchedOutput<T, W>
+where
+    T: IO,
+    W: Write,
+{
+    fn drop(&mut self) {
+        self.flush_quietly();
+    }
+}
+
+/// Talks to programs that speak ASCII: queued text is fed in a byte at a time,
+/// and output is collected as text. Values outside the ASCII range (usually a
+/// final answer) are collected as numbers instead, and appear in the text as
+/// a decimal line of their own.
+#[derive(Debug, Clone, Default)]
+pub struct AsciiIo {
+    input: VecDeque<u8>,
+    output: String,
+    numbers: Vec<i64>,
+    echo: bool,
+}
+
+impl AsciiIo {
+    pub fn new() -> AsciiIo {
+        AsciiIo::default()
+    }
+
+    /// Also print output to stdout as it arrives.
+    pub fn echo(mut self, echo: bool) -> AsciiIo {
+        self.echo = echo;
+        self
+    }
+
+    pub fn queue(&mut self, text: &str) {
+        self.input.extend(text.bytes());
+    }
+
+    /// Queues `line` followed by the newline the programs wait for.
+    pub fn queue_line(&mut self, line: &str) {
+        self.queue(line);
+        self.input.push_back(b'\n');
+    }
+
+    pub fn text(&self) -> &str {
+        &self.output
+    }
+
+    /// Returns the text output so far, leaving none behind.
+    pub fn take_text(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn numbers(&self) -> &[i64] {
+        &self.numbers
+    }
+}
+
+impl IO for AsciiIo {
+    fn input(&mut self) -> i64 {
+        self.try_input()
+            .expect("program wanted more input than was queued")
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        self.input.pop_front().map(i64::from)
+    }
+
+    fn output(&mut self, v: i64) {
+        let text = match u8::try_from(v) {
+            Ok(byte) if byte.is_ascii() => char::from(byte).to_string(),
+            _ => {
+                self.numbers.push(v);
+                format!("{}\n", v)
+            }
+        };
+        if self.echo {
+            print!("{}", text);
+        }
+        self.output.push_str(&text);
+    }
+}
+
+/// Reads input from and sends output to channels, for wiring machines running
+/// on separate threads into each other.
+#[derive(Debug)]
+pub struct ChannelIo {
+    input: Receiver<i64>,
+    output: Sender<i64>,
+}
+
+impl ChannelIo {
+    pub fn new(input: Receiver<i64>, output: Sender<i64>) -> ChannelIo {
+        ChannelIo { input, output }
+    }
+}
+
+impl IO for ChannelIo {
+    fn input(&mut self) -> i64 {
+        self.input
+            .recv()
+            .expect("input channel closed while the program wanted input")
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        match self.input.try_recv() {
+            Ok(v) => Some(v),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+
+    fn output(&mut self, v: i64) {
+        // If whatever was listening has gone away (say, a downstream machine
+        // halted), there's nobody left to care about the value.
+        let _ = self.output.send(v);
+    }
+}
+
+/// Preloaded inputs and collected outputs, for running a program to
+/// completion (or until it wants more input, with `run_until_event`) and then
+/// draining what it said.
+#[derive(Debug, Clone, Default)]
+pub struct QueueIo {
+    inputs: VecDeque<i64>,
+    outputs: VecDeque<i64>,
+}
+
+impl QueueIo {
+    pub fn new() -> QueueIo {
+        QueueIo::default()
+    }
+
+    pub fn push_input(&mut self, v: i64) {
+        self.inputs.push_back(v);
+    }
+
+    pub fn pop_output(&mut self) -> Option<i64> {
+        self.outputs.pop_front()
+    }
+
+    /// Takes back the most recent output, e.g. to send it somewhere else.
+    pub fn pop_last_output(&mut self) -> Option<i64> {
+        self.outputs.pop_back()
+    }
+
+    pub fn has_input(&self) -> bool {
+        !self.inputs.is_empty()
+    }
+}
+
+impl IO for QueueIo {
+    fn input(&mut self) -> i64 {
+        self.try_input()
+            .expect("program wanted more input than was queued")
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        self.inputs.pop_front()
+    }
+
+    fn output(&mut self, v: i64) {
+        self.outputs.push_back(v);
+    }
+}
+
+/// One value crossing the IO boundary, as logged by `Recorder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Input(i64),
+    Output(i64),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Input(v) => write!(f, "in {}", v),
+            Event::Output(v) => write!(f, "out {}", v),
+        }
+    }
+}
+
+impl std::str::FromStr for Event {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Event, String> {
+        let mut words = line.split_whitespace();
+        let event = match words.next() {
+            Some("in") => Event::Input,
+            Some("out") => Event::Output,
+            _ => return Err(format!("expected in or out: {:?}", line)),
+        };
+        match (words.next().map(str::parse), words.next()) {
+            (Some(Ok(v)), None) => Ok(event(v)),
+            _ => Err(format!("expected one number: {:?}", line)),
+        }
+    }
+}
+
+/// Passes everything through to `inner`, logging each input consumed and
+/// output produced to `log` as it happens, one `Event` per line. A session
+/// logged this way can be fed back in with `Replay`.
+pub struct Recorder<T, W>
+where
+    T: IO,
+    W: Write,
+{
+    inner: T,
+    log: W,
+    error: Option<io::Error>,
+}
+
+impl<T, W> Recorder<T, W>
+where
+    T: IO,
+    W: Write,
+{
+    pub fn new(inner: T, log: W) -> Recorder<T, W> {
+        Recorder {
+            inner,
+            log,
+            error: None,
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Flushes the log. Also reports the first error hit while logging, since
+    /// `IO` has no way to return one.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.log.flush()
+    }
+
+    fn record(&mut self, event: Event) {
+        if self.error.is_none() {
+            if let Err(error) = writeln!(self.log, "{}", event) {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+impl<T, W> IO for Recorder<T, W>
+where
+    T: IO,
+    W: Write,
+{
+    fn input(&mut self) -> i64 {
+        let v = self.inner.input();
+        self.record(Event::Input(v));
+        v
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        let v = self.inner.try_input()?;
+        self.record(Event::Input(v));
+        Some(v)
+    }
+
+    fn output(&mut self, v: i64) {
+        self.record(Event::Output(v));
+        self.inner.output(v);
+    }
+}
+
+/// Where a replayed run first parted ways with its recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the output, counting from zero.
+    pub index: usize,
+    /// None if the run produced more outputs than were recorded.
+    pub expected: Option<i64>,
+    /// None if the run stopped short of the recording.
+    pub actual: Option<i64>,
+}
+
+/// Feeds a recorded session's inputs back in, in order, and checks the
+/// outputs produced against the recorded ones, e.g. to turn a session with
+/// an interactive program into a regression test.
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    inputs: VecDeque<i64>,
+    expected: Vec<i64>,
+    outputs: Vec<i64>,
+}
+
+impl Replay {
+    pub fn new<I>(events: I) -> Replay
+    where
+        I: IntoIterator<Item = Event>,
+    {
+        let mut replay = Replay::default();
+        for event in events {
+            match event {
+                Event::Input(v) => replay.inputs.push_back(v),
+                Event::Output(v) => replay.expected.push(v),
+            }
+        }
+        replay
+    }
+
+    /// Reads a session in the format written by `Recorder`.
+    pub fn from_reader<R: BufRead>(log: R) -> io::Result<Replay> {
+        let mut events = Vec::new();
+        for line in log.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event = line
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            events.push(event);
+        }
+        Ok(Replay::new(events))
+    }
+
+    /// The outputs produced so far.
+    pub fn outputs(&self) -> &[i64] {
+        &self.outputs
+    }
+
+    /// Checks everything produced against the recording. Call it once the
+    /// run is over: a run that's still going may just not have caught up.
+    pub fn verify(&self) -> Result<(), Divergence> {
+        let len = self.expected.len().max(self.outputs.len());
+        match (0..len).find(|&i| self.expected.get(i) != self.outputs.get(i)) {
+            None => Ok(()),
+            Some(index) => Err(Divergence {
+                index,
+                expected: self.expected.get(index).cloned(),
+                actual: self.outputs.get(index).cloned(),
+            }),
+        }
+    }
+}
+
+impl IO for Replay {
+    fn input(&mut self) -> i64 {
+        self.try_input()
+            .expect("program wanted more input than was recorded")
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        self.inputs.pop_front()
+    }
+
+    fn output(&mut self, v: i64) {
+        self.outputs.push(v);
+    }
+}
+
+/// Inputs from an iterator, outputs collected into a Vec, so that a run can be
+/// set up inline as `(inputs.into_iter(), Vec::new())`.
+impl<I> IO for (I, Vec<i64>)
+where
+    I: Iterator<Item = i64>,
+{
+    fn input(&mut self) -> i64 {
+        self.0
+            .next()
+            .expect("program wanted more input than was given")
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        self.0.next()
+    }
+
+    fn output(&mut self, v: i64) {
+        self.1.push(v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Intcode, StopReason};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn ascii_round_trip() {
+        // Echo two bytes of input, then output a value that isn't ASCII.
+        let program = vec![3, 13, 4, 13, 3, 13, 4, 13, 104, 1000, 104, 10, 99, 0];
+        let mut io = AsciiIo::new();
+        io.queue_line("A");
+        Intcode::new(program, &mut io).run().unwrap();
+        assert_eq!(io.text(), "A\n1000\n\n");
+        assert_eq!(io.numbers(), [1000]);
+        assert_eq!(io.take_text(), "A\n1000\n\n");
+        assert_eq!(io.text(), "");
+    }
+
+    #[test]
+    fn channels_between_threads() {
+        let (input, rx) = channel();
+        let (tx, output) = channel();
+        // Doubles each input until it sees a zero.
+        let program = vec![
+            3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+        ];
+        let machine = std::thread::spawn(move || {
+            Intcode::new(program, ChannelIo::new(rx, tx)).run().unwrap();
+        });
+        for v in &[1, 5, -3] {
+            input.send(*v).unwrap();
+        }
+        input.send(0).unwrap();
+        machine.join().unwrap();
+        assert_eq!(output.iter().collect::<Vec<_>>(), [2, 10, -6]);
+    }
+
+    #[test]
+    fn record_and_replay() {
+        // Adds pairs of inputs until it sees a zero.
+        let program = vec![
+            3, 17, 1006, 17, 16, 3, 18, 1, 17, 18, 19, 4, 19, 1105, 1, 0, 99, 0, 0, 0,
+        ];
+        let mut log = Vec::new();
+        let inputs = vec![1, 2, 30, 12, 0];
+        let mut recorder = Recorder::new((inputs.into_iter(), Vec::new()), &mut log);
+        Intcode::new(program.clone(), &mut recorder).run().unwrap();
+        recorder.flush().unwrap();
+        assert_eq!(recorder.inner().1, [3, 42]);
+        drop(recorder);
+        assert_eq!(
+            String::from_utf8(log.clone()).unwrap(),
+            "in 1\nin 2\nout 3\nin 30\nin 12\nout 42\nin 0\n"
+        );
+
+        let mut replay = Replay::from_reader(&log[..]).unwrap();
+        Intcode::new(program.clone(), &mut replay).run().unwrap();
+        assert_eq!(replay.verify(), Ok(()));
+
+        // The same session against a program that multiplies instead.
+        let mut multiply = program;
+        multiply[7] = 2;
+        let mut replay = Replay::from_reader(&log[..]).unwrap();
+        Intcode::new(multiply, &mut replay).run().unwrap();
+        assert_eq!(
+            replay.verify(),
+            Err(Divergence {
+                index: 0,
+                expected: Some(3),
+                actual: Some(2),
+            })
+        );
+        assert!(Replay::from_reader(&b"in 1\nsideways 2\n"[..]).is_err());
+    }
+
+    // Keeps each write to it as a separate chunk, shared so that it can be
+    // looked at while a `BatchedOutput` owns it.
+    #[derive(Clone, Default)]
+    struct Chunks(std::rc::Rc<std::cell::RefCell<Vec<String>>>);
+
+    impl Chunks {
+        fn take(&self) -> Vec<String> {
+            self.0.borrow_mut().drain(..).collect()
+        }
+    }
+
+    impl Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let chunk = String::from_utf8(buf.to_vec()).unwrap();
+            self.0.borrow_mut().push(chunk);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn batched_output() {
+        // Outputs 1 to 7.
+        let program = crate::asm::assemble(
+            "
+            loop:
+                add n, #1, n
+                out n
+                lt n, #7, t
+                jnz t, #loop
+                halt
+            ",
+        )
+        .unwrap();
+        let chunks = Chunks::default();
+        let mut io = BatchedOutput::new(QueueIo::new(), chunks.clone(), 3);
+        Intcode::new(program, &mut io).run().unwrap();
+        assert_eq!(chunks.take(), ["1\n2\n3\n", "4\n5\n6\n"]);
+        // The partial batch left at the halt goes out when the wrapper does.
+        drop(io);
+        assert_eq!(chunks.take(), ["7\n"]);
+
+        // Pending output is written out before the program waits for input.
+        let chunks = Chunks::default();
+        let mut inner = QueueIo::new();
+        inner.push_input(5);
+        let mut io = BatchedOutput::new(inner, chunks.clone(), 10);
+        let mut machine = Intcode::new(vec![104, 1, 3, 9, 4, 9, 104, 2, 99, 0], &mut io);
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Output(1)));
+        assert!(chunks.take().is_empty());
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Output(5)));
+        assert_eq!(chunks.take(), ["1\n"]);
+        assert_eq!(machine.run(), Ok(StopReason::Halted));
+        io.flush().unwrap();
+        assert_eq!(chunks.take(), ["5\n2\n"]);
+        drop(io);
+        assert!(chunks.take().is_empty());
+    }
+
+    #[test]
+    fn batched_output_reports_write_errors() {
+        struct Broken;
+
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("broken"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut io = BatchedOutput::new(QueueIo::new(), Broken, 1);
+        io.output(1);
+        io.output(2);
+        assert_eq!(io.flush().unwrap_err().to_string(), "broken");
+    }
+
+    #[test]
+    fn queue_until_input_runs_out() {
+        let mut io = QueueIo::new();
+        io.push_input(3);
+        // Echoes inputs forever.
+        let mut machine = Intcode::new(vec![3, 7, 4, 7, 1105, 1, 0, 0], &mut io);
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Output(3)));
+        assert_eq!(machine.run_until_event(), Ok(StopReason::NeedsInput));
+        machine.io_mut().push_input(4);
+        machine.io_mut().push_input(5);
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Output(4)));
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Output(5)));
+        assert_eq!(io.pop_output(), Some(3));
+        assert_eq!(io.pop_output(), Some(4));
+        assert_eq!(io.pop_output(), Some(5));
+        assert_eq!(io.pop_output(), None);
+    }
+}
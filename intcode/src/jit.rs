@@ -0,0 +1,1056 @@
+//! A native-code tier for the VM, behind the `jit` feature. `Jit` runs a
+//! machine just as `Intcode::run` would, but once a jump target has been
+//! reached often enough, the code around it is compiled with Cranelift and
+//! run natively from then on. That code is a region: every instruction
+//! reachable from the target through arithmetic, comparisons, relative base
+//! changes and jumps to constant addresses, so a loop usually runs start to
+//! finish without coming back out.
+//!
+//! Compiled code only takes the fast path. At the first instruction that
+//! needs anything else (IO, an address outside dense memory, an overflow, a
+//! write into the region's own code) it stops just before it and the
+//! interpreter takes over, so errors, costs and the overflow policy all come
+//! out exactly as they would without the JIT. Each region is checked against
+//! the words it was compiled from every time it's entered, so it's safe to
+//! run self-modifying programs, or a different program altogether, through
+//! the same `Jit`.
+
+use crate::{Intcode, RawWords, RunError, StopReason, IO};
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::types::I64;
+use cranelift_codegen::ir::{AbiParam, Block as IrBlock, InstBuilder, MemFlags, Value};
+use cranelift_codegen::Context as CodegenContext;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+use std::collections::{BTreeMap, HashMap};
+use std::mem::{offset_of, ManuallyDrop};
+
+// How many times the interpreter has to reach a jump target before the code
+// there is compiled.
+const DEFAULT_THRESHOLD: u32 = 64;
+
+// Most instructions in one region.
+const MAX_REGION: usize = 256;
+
+// A region that keeps being rewritten stops being compiled after this many
+// tries, so a program rewriting its own inner loop can't keep generating code.
+const MAX_COMPILES: u32 = 4;
+
+// Instructions compiled code runs before handing back, checked at jumps, so
+// that callers stepping through a budget get a look in.
+const FUEL: usize = 1 << 12;
+
+// Every instruction writes at most once, and a region can't loop without a
+// jump, so this is enough to log every write a call makes.
+const WRITE_LOG: usize = FUEL + MAX_REGION;
+
+/// Counts of what the JIT has done, for checking that it's earning its keep.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JitStats {
+    /// Regions compiled, including recompiles after their code changed.
+    pub compiled: usize,
+    /// Regions thrown away because their code changed.
+    pub invalidated: usize,
+    /// Instructions executed natively rather than by the interpreter.
+    pub native_instructions: u64,
+}
+
+// The machine state a region reads and updates, passed by pointer.
+#[repr(C)]
+struct Context {
+    memory: *mut i64,
+    len: u64,
+    // Whether `memory` can be written in place.
+    writable: u64,
+    relative_base: i64,
+    // Writes below here are logged, so that the interpreter can drop
+    // anything it decoded from them.
+    decoded: u64,
+    written: *mut u64,
+    written_len: u64,
+    // Cost model weights, in `WEIGHTS` order.
+    weights: [u64; 7],
+    executed: u64,
+    cost: u64,
+    // Whether it stopped before an instruction it couldn't run.
+    bailed: u64,
+}
+
+// The opcodes compiled code runs, in the order of `Context::weights`.
+const WEIGHTS: [i64; 7] = [1, 2, 5, 6, 7, 8, 9];
+
+type Compiled = extern "C" fn(*mut Context) -> i64;
+
+struct Region {
+    // Runs of consecutive instruction words, and their addresses.
+    code: Vec<(usize, Vec<i64>)>,
+    function: Compiled,
+}
+
+impl Region {
+    fn matches(&self, memory: &[i64]) -> bool {
+        self.code
+            .iter()
+            .all(|(start, words)| memory.get(*start..start + words.len()) == Some(&words[..]))
+    }
+}
+
+pub struct Jit {
+    module: ManuallyDrop<JITModule>,
+    codegen: CodegenContext,
+    builder: FunctionBuilderContext,
+    threshold: u32,
+    // Indexed by pc, as far as the highest one reached.
+    slots: Vec<Slot>,
+    // Whether the pc was just jumped (or fallen through) to, which is where
+    // regions start.
+    at_target: bool,
+    written: Vec<u64>,
+    stats: JitStats,
+}
+
+// What's known about the region starting at one address.
+#[derive(Default)]
+struct Slot {
+    region: Option<Region>,
+    // Times the interpreter has reached it as a jump target.
+    heat: u32,
+    compiles: u32,
+    // Nothing to compile here, or rewritten too often.
+    uncompiled: bool,
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Jit::new()
+    }
+}
+
+impl Jit {
+    /// Panics if Cranelift can't generate code for this machine.
+    pub fn new() -> Jit {
+        Jit::with_threshold(DEFAULT_THRESHOLD)
+    }
+
+    /// Compiles the code at a jump target once the interpreter has reached
+    /// it `threshold` times (at least once).
+    pub fn with_threshold(threshold: u32) -> Jit {
+        let builder = JITBuilder::with_flags(&[("opt_level", "speed")], default_libcall_names())
+            .expect("no Cranelift backend for this machine");
+        let module = JITModule::new(builder);
+        Jit {
+            codegen: module.make_context(),
+            module: ManuallyDrop::new(module),
+            builder: FunctionBuilderContext::new(),
+            threshold: threshold.max(1),
+            slots: Vec::new(),
+            at_target: true,
+            written: vec![0; WRITE_LOG],
+            stats: JitStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> JitStats {
+        self.stats
+    }
+
+    /// Like `Intcode::run`. Breakpoints and coverage tracking work as usual,
+    /// but while either is on, everything is interpreted.
+    pub fn run<T: IO>(&mut self, machine: &mut Intcode<T>) -> Result<StopReason, RunError> {
+        self.at_target = true;
+        loop {
+            if let Some(reason) = self.advance(machine, false)? {
+                return Ok(reason);
+            }
+        }
+    }
+
+    /// Like `Intcode::run_until_event`; see `run`.
+    pub fn run_until_event<T: IO>(
+        &mut self,
+        machine: &mut Intcode<T>,
+    ) -> Result<StopReason, RunError> {
+        self.at_target = true;
+        loop {
+            if let Some(reason) = self.advance(machine, true)? {
+                return Ok(reason);
+            }
+        }
+    }
+
+    // Runs compiled code if there's some to run, otherwise interprets one
+    // instruction.
+    fn advance<T: IO>(
+        &mut self,
+        machine: &mut Intcode<T>,
+        poll: bool,
+    ) -> Result<Option<StopReason>, RunError> {
+        if machine.breakpoints.is_empty() && machine.coverage.is_none() && self.native(machine) {
+            return Ok(None);
+        }
+        let opcode = machine.memory.get(machine.pc) % 100;
+        let reason = machine.advance(poll)?;
+        self.at_target = opcode == 5 || opcode == 6;
+        Ok(reason)
+    }
+
+    // Runs the region at the pc, and any it leads straight into, until one
+    // stops for the interpreter or the fuel runs out. Returns whether
+    // anything ran.
+    fn native<T: IO>(&mut self, machine: &mut Intcode<T>) -> bool {
+        let weights = match &machine.cost_model {
+            Some(model) => [
+                model.add,
+                model.multiply,
+                model.jump_if_true,
+                model.jump_if_false,
+                model.less_than,
+                model.equals,
+                model.relative_base_offset,
+            ],
+            None => [0; 7],
+        };
+        let mut executed = 0;
+        loop {
+            let pc = machine.pc;
+            if !self.prepare(machine.memory.dense(), pc) {
+                return executed > 0;
+            }
+            let region = self.slots[pc].region.as_ref().unwrap();
+            let decoded = machine.decoded.len() as u64;
+            // A shared image is only read through this pointer: the first
+            // write bails, and the interpreter copies it.
+            let writable = !machine.memory.is_shared();
+            let (memory, len) = if writable {
+                let dense = machine.memory.dense_mut();
+                (dense.as_mut_ptr(), dense.len())
+            } else {
+                let dense = machine.memory.dense();
+                (dense.as_ptr() as *mut i64, dense.len())
+            };
+            let mut context = Context {
+                memory,
+                len: len as u64,
+                writable: writable as u64,
+                relative_base: machine.relative_base,
+                decoded,
+                written: self.written.as_mut_ptr(),
+                written_len: 0,
+                weights,
+                executed: 0,
+                cost: 0,
+                bailed: 0,
+            };
+            let next = (region.function)(&mut context);
+            machine.pc = next as usize;
+            machine.relative_base = context.relative_base;
+            for &address in &self.written[..context.written_len as usize] {
+                machine.forget_decoded(address as usize);
+            }
+            if machine.cost_model.is_some() {
+                machine.cost += context.cost;
+            }
+            self.stats.native_instructions += context.executed;
+            executed += context.executed;
+            self.at_target = context.bailed == 0;
+            if context.bailed != 0 || context.executed == 0 || executed >= FUEL as u64 {
+                return executed > 0;
+            }
+        }
+    }
+
+    // Checks that the region at `pc` still matches `memory`, or compiles one
+    // if it's just become hot. Returns whether there's a region to run.
+    fn prepare(&mut self, memory: &[i64], pc: usize) -> bool {
+        if pc >= memory.len() {
+            return false;
+        }
+        if pc >= self.slots.len() {
+            self.slots.resize_with(pc + 1, Slot::default);
+        }
+        let slot = &mut self.slots[pc];
+        if let Some(region) = &slot.region {
+            if region.matches(memory) {
+                return true;
+            }
+            slot.region = None;
+            self.stats.invalidated += 1;
+        }
+        if !self.at_target || slot.uncompiled {
+            return false;
+        }
+        slot.heat += 1;
+        if slot.heat < self.threshold {
+            return false;
+        }
+        slot.heat = 0;
+        slot.compiles += 1;
+        if slot.compiles > MAX_COMPILES {
+            slot.uncompiled = true;
+            return false;
+        }
+        let region = self.compile(memory, pc);
+        let slot = &mut self.slots[pc];
+        slot.uncompiled = region.is_none();
+        slot.region = region;
+        self.stats.compiled += !slot.uncompiled as usize;
+        !slot.uncompiled
+    }
+
+    // The instruction at `pc`, if compiled code can run it: arithmetic,
+    // comparisons, jumps and relative base changes, with valid modes and
+    // with all their words in memory.
+    fn decode(memory: &[i64], pc: usize) -> Option<RawWords> {
+        let instruction = *memory.get(pc)?;
+        let (inputs, writes) = match instruction % 100 {
+            1 | 2 | 7 | 8 => (2, true),
+            5 | 6 => (2, false),
+            9 => (1, false),
+            _ => return None,
+        };
+        let len = 1 + inputs + writes as usize;
+        let words = memory.get(pc + 1..pc + len)?;
+        let mut params = [0; 3];
+        params[..words.len()].copy_from_slice(words);
+        let raw = RawWords::new(instruction, params);
+        let modes_ok = raw.modes[..inputs].iter().all(|&mode| mode <= 2);
+        if !modes_ok || (writes && raw.modes[2] != 0 && raw.modes[2] != 2) {
+            return None;
+        }
+        Some(raw)
+    }
+
+    // The instructions reachable from `start` that compiled code can run.
+    fn scan(memory: &[i64], start: usize) -> BTreeMap<usize, RawWords> {
+        let mut ops = BTreeMap::new();
+        let mut pending = vec![start];
+        while let Some(pc) = pending.pop() {
+            if ops.len() == MAX_REGION || ops.contains_key(&pc) {
+                continue;
+            }
+            let raw = match Jit::decode(memory, pc) {
+                Some(raw) => raw,
+                None => continue,
+            };
+            ops.insert(pc, raw);
+            if (raw.opcode == 5 || raw.opcode == 6) && raw.modes[1] == 1 && raw.params[1] >= 0 {
+                pending.push(raw.params[1] as usize);
+            }
+            pending.push(pc + len(&raw));
+        }
+        ops
+    }
+
+    fn compile(&mut self, memory: &[i64], start: usize) -> Option<Region> {
+        let ops = Jit::scan(memory, start);
+        if ops.is_empty() {
+            return None;
+        }
+        let mut code: Vec<(usize, usize)> = Vec::new();
+        for (&pc, raw) in &ops {
+            let end = pc + len(raw);
+            match code.last_mut() {
+                Some((_, last)) if pc <= *last => *last = end.max(*last),
+                _ => code.push((pc, end)),
+            }
+        }
+
+        let mut signature = self.module.make_signature();
+        signature.params.push(AbiParam::new(I64));
+        signature.returns.push(AbiParam::new(I64));
+        let id = self
+            .module
+            .declare_anonymous_function(&signature)
+            .expect("couldn't declare a region");
+        self.codegen.func.signature = signature;
+        let builder = FunctionBuilder::new(&mut self.codegen.func, &mut self.builder);
+        Emitter::new(builder, &ops, code.clone()).emit(&ops, start);
+        self.module
+            .define_function(id, &mut self.codegen)
+            .expect("couldn't compile a region");
+        self.module.clear_context(&mut self.codegen);
+        self.module
+            .finalize_definitions()
+            .expect("couldn't finalize a region");
+        let function = self.module.get_finalized_function(id);
+        // Safety: the code was just generated from a function with this
+        // signature, and lives as long as the module, which outlives every
+        // region.
+        let function = unsafe { std::mem::transmute::<*const u8, Compiled>(function) };
+        Some(Region {
+            code: code
+                .into_iter()
+                .map(|(start, end)| (start, memory[start..end].to_vec()))
+                .collect(),
+            function,
+        })
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        // Safety: regions only run while the Jit is borrowed, so none are
+        // running now, and their function pointers go with it.
+        unsafe { ManuallyDrop::take(&mut self.module).free_memory() }
+    }
+}
+
+// Words in an instruction compiled code can run.
+fn len(raw: &RawWords) -> usize {
+    match raw.opcode {
+        5 | 6 => 3,
+        9 => 2,
+        _ => 4,
+    }
+}
+
+// Generates the code for one region.
+struct Emitter<'a> {
+    b: FunctionBuilder<'a>,
+    // The region's own code, which it mustn't write to.
+    code: Vec<(usize, usize)>,
+    // The code for the instruction at each pc.
+    blocks: HashMap<usize, IrBlock>,
+    context: Value,
+    memory: Value,
+    len: Value,
+    writable: Value,
+    decoded: Value,
+    written: Value,
+    weights: [Value; 7],
+    relative_base: Variable,
+    written_len: Variable,
+    executed: Variable,
+    cost: Variable,
+    // Takes (next pc, whether it bailed).
+    exit: IrBlock,
+}
+
+impl<'a> Emitter<'a> {
+    fn new(
+        mut b: FunctionBuilder<'a>,
+        ops: &BTreeMap<usize, RawWords>,
+        code: Vec<(usize, usize)>,
+    ) -> Emitter<'a> {
+        let entry = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+        let context = b.block_params(entry)[0];
+        let load = |b: &mut FunctionBuilder, offset: usize| {
+            b.ins()
+                .load(I64, MemFlags::trusted(), context, offset as i32)
+        };
+        let memory = load(&mut b, offset_of!(Context, memory));
+        let len = load(&mut b, offset_of!(Context, len));
+        let writable = load(&mut b, offset_of!(Context, writable));
+        let decoded = load(&mut b, offset_of!(Context, decoded));
+        let written = load(&mut b, offset_of!(Context, written));
+        let mut weights = [memory; 7];
+        for (n, weight) in weights.iter_mut().enumerate() {
+            *weight = load(&mut b, offset_of!(Context, weights) + 8 * n);
+        }
+        let variable = |b: &mut FunctionBuilder, n, value| {
+            let variable = Variable::from_u32(n);
+            b.declare_var(variable, I64);
+            b.def_var(variable, value);
+            variable
+        };
+        let value = load(&mut b, offset_of!(Context, relative_base));
+        let relative_base = variable(&mut b, 0, value);
+        let zero = b.ins().iconst(I64, 0);
+        let written_len = variable(&mut b, 1, zero);
+        let executed = variable(&mut b, 2, zero);
+        let cost = variable(&mut b, 3, zero);
+        let blocks = ops.keys().map(|&pc| (pc, b.create_block())).collect();
+        let exit = b.create_block();
+        b.append_block_param(exit, I64);
+        b.append_block_param(exit, I64);
+        Emitter {
+            b,
+            code,
+            blocks,
+            context,
+            memory,
+            len,
+            writable,
+            decoded,
+            written,
+            weights,
+            relative_base,
+            written_len,
+            executed,
+            cost,
+            exit,
+        }
+    }
+
+    fn constant(&mut self, v: i64) -> Value {
+        self.b.ins().iconst(I64, v)
+    }
+
+    // Leaves the region for `next`, which the interpreter has to run if
+    // `bailed`.
+    fn leave(&mut self, next: Value, bailed: bool) {
+        let bailed = self.constant(bailed as i64);
+        self.b.ins().jump(self.exit, &[next, bailed]);
+    }
+
+    // Carries on at `pc`, inside the region if it's there.
+    fn goto(&mut self, pc: usize) {
+        match self.blocks.get(&pc) {
+            Some(&block) => {
+                self.b.ins().jump(block, &[]);
+            }
+            None => {
+                let next = self.constant(pc as i64);
+                self.leave(next, false);
+            }
+        }
+    }
+
+    // Hands back to the interpreter before the instruction at `pc` if `bail`
+    // is true.
+    fn guard(&mut self, bail: Value, pc: usize) {
+        let pc = self.constant(pc as i64);
+        let bailed = self.constant(1);
+        let carry_on = self.b.create_block();
+        self.b
+            .ins()
+            .brif(bail, self.exit, &[pc, bailed], carry_on, &[]);
+        self.b.switch_to_block(carry_on);
+    }
+
+    // Counts the instruction just finished.
+    fn count(&mut self, opcode: i64) {
+        let executed = self.b.use_var(self.executed);
+        let executed = self.b.ins().iadd_imm(executed, 1);
+        self.b.def_var(self.executed, executed);
+        let weight = self.weights[WEIGHTS.iter().position(|&o| o == opcode).unwrap()];
+        let cost = self.b.use_var(self.cost);
+        let cost = self.b.ins().iadd(cost, weight);
+        self.b.def_var(self.cost, cost);
+    }
+
+    fn address(&mut self, raw: &RawWords, param: usize, pc: usize) -> Value {
+        let value = self.constant(raw.params[param]);
+        let address = if raw.modes[param] == 2 {
+            let base = self.b.use_var(self.relative_base);
+            let (address, overflow) = self.b.ins().sadd_overflow(base, value);
+            self.guard(overflow, pc);
+            address
+        } else {
+            value
+        };
+        // Negative addresses come out huge, so this catches them too.
+        let outside = self
+            .b
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, address, self.len);
+        self.guard(outside, pc);
+        address
+    }
+
+    fn word(&mut self, base: Value, index: Value) -> Value {
+        let offset = self.b.ins().ishl_imm(index, 3);
+        self.b.ins().iadd(base, offset)
+    }
+
+    fn read(&mut self, raw: &RawWords, param: usize, pc: usize) -> Value {
+        if raw.modes[param] == 1 {
+            return self.constant(raw.params[param]);
+        }
+        let address = self.address(raw, param, pc);
+        let word = self.word(self.memory, address);
+        self.b.ins().load(I64, MemFlags::trusted(), word, 0)
+    }
+
+    fn write(&mut self, raw: &RawWords, pc: usize, value: Value) {
+        let read_only = self.b.ins().icmp_imm(IntCC::Equal, self.writable, 0);
+        self.guard(read_only, pc);
+        let address = self.address(raw, 2, pc);
+        for (start, end) in self.code.clone() {
+            let offset = self.b.ins().iadd_imm(address, -(start as i64));
+            let own_code =
+                self.b
+                    .ins()
+                    .icmp_imm(IntCC::UnsignedLessThan, offset, (end - start) as i64);
+            self.guard(own_code, pc);
+        }
+        let word = self.word(self.memory, address);
+        self.b.ins().store(MemFlags::trusted(), value, word, 0);
+
+        // The interpreter only needs to hear about writes to words an
+        // instruction it's decoded might start up to three words before.
+        let (log, done) = (self.b.create_block(), self.b.create_block());
+        let near = self.b.ins().iadd_imm(self.decoded, 3);
+        let decoded = self.b.ins().icmp(IntCC::UnsignedLessThan, address, near);
+        self.b.ins().brif(decoded, log, &[], done, &[]);
+        self.b.switch_to_block(log);
+        let logged = self.b.use_var(self.written_len);
+        let entry = self.word(self.written, logged);
+        self.b.ins().store(MemFlags::trusted(), address, entry, 0);
+        let logged = self.b.ins().iadd_imm(logged, 1);
+        self.b.def_var(self.written_len, logged);
+        self.b.ins().jump(done, &[]);
+        self.b.switch_to_block(done);
+    }
+
+    fn emit(mut self, ops: &BTreeMap<usize, RawWords>, start: usize) {
+        let first = self.blocks[&start];
+        self.b.ins().jump(first, &[]);
+        for (&pc, raw) in ops {
+            let block = self.blocks[&pc];
+            self.b.switch_to_block(block);
+            match raw.opcode {
+                1 | 2 => {
+                    let a = self.read(raw, 0, pc);
+                    let b = self.read(raw, 1, pc);
+                    let (result, overflow) = if raw.opcode == 1 {
+                        self.b.ins().sadd_overflow(a, b)
+                    } else {
+                        self.b.ins().smul_overflow(a, b)
+                    };
+                    self.guard(overflow, pc);
+                    self.write(raw, pc, result);
+                }
+                7 | 8 => {
+                    let a = self.read(raw, 0, pc);
+                    let b = self.read(raw, 1, pc);
+                    let cc = if raw.opcode == 7 {
+                        IntCC::SignedLessThan
+                    } else {
+                        IntCC::Equal
+                    };
+                    let test = self.b.ins().icmp(cc, a, b);
+                    let result = self.b.ins().uextend(I64, test);
+                    self.write(raw, pc, result);
+                }
+                9 => {
+                    let incr = self.read(raw, 0, pc);
+                    let base = self.b.use_var(self.relative_base);
+                    let (base, overflow) = self.b.ins().sadd_overflow(base, incr);
+                    self.guard(overflow, pc);
+                    self.b.def_var(self.relative_base, base);
+                }
+                _ => {
+                    self.jump(raw, pc);
+                    continue;
+                }
+            }
+            self.count(raw.opcode);
+            self.goto(pc + len(raw));
+        }
+
+        self.b.switch_to_block(self.exit);
+        let next = self.b.block_params(self.exit)[0];
+        let bailed = self.b.block_params(self.exit)[1];
+        let stores = [
+            (
+                self.b.use_var(self.relative_base),
+                offset_of!(Context, relative_base),
+            ),
+            (
+                self.b.use_var(self.written_len),
+                offset_of!(Context, written_len),
+            ),
+            (self.b.use_var(self.executed), offset_of!(Context, executed)),
+            (self.b.use_var(self.cost), offset_of!(Context, cost)),
+            (bailed, offset_of!(Context, bailed)),
+        ];
+        for &(value, offset) in &stores {
+            self.b
+                .ins()
+                .store(MemFlags::trusted(), value, self.context, offset as i32);
+        }
+        self.b.ins().return_(&[next]);
+        self.b.seal_all_blocks();
+        self.b.finalize();
+    }
+
+    fn jump(&mut self, raw: &RawWords, pc: usize) {
+        // The interpreter decodes the target even when the jump isn't
+        // taken, so a bad one fails either way.
+        let condition = self.read(raw, 0, pc);
+        let target = self.read(raw, 1, pc);
+        let cc = if raw.opcode == 5 {
+            IntCC::NotEqual
+        } else {
+            IntCC::Equal
+        };
+        let taken = self.b.ins().icmp_imm(cc, condition, 0);
+        let (jump, fall_through) = (self.b.create_block(), self.b.create_block());
+        self.b.ins().brif(taken, jump, &[], fall_through, &[]);
+
+        self.b.switch_to_block(fall_through);
+        self.count(raw.opcode);
+        self.goto(pc + 3);
+
+        self.b.switch_to_block(jump);
+        let negative = self.b.ins().icmp_imm(IntCC::SignedLessThan, target, 0);
+        self.guard(negative, pc);
+        self.count(raw.opcode);
+        let inside = match raw.modes[1] {
+            1 => self.blocks.get(&(raw.params[1] as usize)).cloned(),
+            _ => None,
+        };
+        match inside {
+            // Loops all go through here, so this is where fuel runs out.
+            Some(block) => {
+                let executed = self.b.use_var(self.executed);
+                let empty =
+                    self.b
+                        .ins()
+                        .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, executed, FUEL as i64);
+                let bailed = self.constant(0);
+                self.b
+                    .ins()
+                    .brif(empty, self.exit, &[target, bailed], block, &[]);
+            }
+            None => self.leave(target, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::io::QueueIo;
+    use crate::parse::parse_program;
+    use crate::{CostModel, OverflowPolicy};
+    use proptest::prelude::*;
+    use std::sync::Arc;
+
+    const PRIME: &str = include_str!("../samples/prime.intcode");
+    const SIEVE: &str = include_str!("../samples/bench/sieve.intcode");
+    const DEEP_RECURSION: &str = include_str!("../samples/bench/deep_recursion.intcode");
+
+    fn machine(program: &[i64], inputs: &[i64]) -> Intcode<QueueIo> {
+        let mut io = QueueIo::new();
+        for &v in inputs {
+            io.push_input(v);
+        }
+        let mut machine = Intcode::new(program.to_vec(), io);
+        machine.set_cost_model(CostModel::default());
+        machine
+    }
+
+    fn outputs(machine: &mut Intcode<QueueIo>) -> Vec<i64> {
+        let io = machine.io_mut();
+        std::iter::from_fn(|| io.pop_output()).collect()
+    }
+
+    // Runs `program` to the end with and without the JIT, checks that the
+    // two machines finish in the same state, and returns how it ended and
+    // what it output.
+    fn compare(
+        jit: &mut Jit,
+        program: &[i64],
+        inputs: &[i64],
+        setup: &dyn Fn(&mut Intcode<QueueIo>),
+    ) -> (Result<StopReason, RunError>, Vec<i64>) {
+        let mut interpreted = machine(program, inputs);
+        setup(&mut interpreted);
+        let expected = interpreted.run();
+        let mut compiled = machine(program, inputs);
+        setup(&mut compiled);
+        assert_eq!(jit.run(&mut compiled), expected);
+        assert_eq!(compiled.snapshot(), interpreted.snapshot());
+        assert_eq!(compiled.cost(), interpreted.cost());
+        let out = outputs(&mut compiled);
+        assert_eq!(out, outputs(&mut interpreted));
+        (expected, out)
+    }
+
+    #[test]
+    fn samples() {
+        let cases: [(&str, &[i64], i64); 4] = [
+            (SIEVE, &[100_000], 454_396_537),
+            (DEEP_RECURSION, &[3, 6], 509),
+            (PRIME, &[7919], 1),
+            (PRIME, &[7917], 0),
+        ];
+        for &(text, inputs, answer) in &cases {
+            let mut jit = Jit::new();
+            let program = parse_program(text).unwrap();
+            let (end, out) = compare(&mut jit, &program, inputs, &|_| ());
+            assert_eq!((end, out), (Ok(StopReason::Halted), vec![answer]));
+            let stats = jit.stats();
+            assert!(stats.compiled > 0);
+            assert_eq!(stats.invalidated, 0);
+            assert!(stats.native_instructions > 0);
+        }
+    }
+
+    #[test]
+    fn rewritten_blocks() {
+        // The block at `step` rewrites the immediate in the block at `loop`,
+        // so that acc goes up by 1, 2, 3, ...
+        let program = assemble(
+            "
+                in n
+            loop:
+                add acc, #1, acc
+                jnz #1, #step
+            step:
+                add 4, #1, 4
+                add n, #-1, n
+                jnz n, #loop
+                out acc
+                halt
+            ",
+        )
+        .unwrap();
+        let mut jit = Jit::with_threshold(2);
+        let (_, out) = compare(&mut jit, &program, &[20], &|_| ());
+        assert_eq!(out, [210]);
+        let stats = jit.stats();
+        assert!(stats.invalidated > 0);
+        // Both regions contain the rewritten word, so both give up.
+        assert_eq!(stats.compiled as u32, 2 * MAX_COMPILES);
+
+        // Here the block rewrites its own next instruction, which it leaves
+        // for the interpreter to do.
+        let program = assemble(
+            "
+                in n
+            loop:
+                add acc, #1, acc
+                add 4, #1, 4
+                add n, #-1, n
+                jnz n, #loop
+                out acc
+                halt
+            ",
+        )
+        .unwrap();
+        let mut jit = Jit::with_threshold(1);
+        let (_, out) = compare(&mut jit, &program, &[20], &|_| ());
+        assert_eq!(out, [210]);
+    }
+
+    #[test]
+    fn growing_and_far_memory() {
+        let program = assemble(
+            "
+                in n
+            loop:
+                arb #1
+                add n, #0, rb+100
+                add n, #0, 1000000000
+                add n, #-1, n
+                jnz n, #loop
+                out 1000000000
+                out rb+100
+                halt
+            ",
+        )
+        .unwrap();
+        let mut jit = Jit::with_threshold(1);
+        let (_, out) = compare(&mut jit, &program, &[300], &|_| ());
+        assert_eq!(out, [1, 1]);
+        assert!(jit.stats().native_instructions > 0);
+    }
+
+    #[test]
+    fn overflow_policies() {
+        // Triples x until it overflows.
+        let program = assemble(
+            "
+                add #1, #0, x
+            loop:
+                mul x, #3, x
+                add n, #1, n
+                lt n, #50, t
+                jnz t, #loop
+                out x
+                halt
+            ",
+        )
+        .unwrap();
+        let mut jit = Jit::with_threshold(1);
+        let policies = [
+            OverflowPolicy::Error,
+            OverflowPolicy::Wrap,
+            OverflowPolicy::Saturate,
+        ];
+        let ends: Vec<_> = policies
+            .iter()
+            .map(|&policy| {
+                compare(&mut jit, &program, &[], &|machine| {
+                    machine.set_overflow_policy(policy)
+                })
+            })
+            .collect();
+        assert_eq!(ends[0].0, Err(RunError::Overflow { pc: 4 }));
+        assert_eq!(ends[2].1, [i64::MAX]);
+        assert!(jit.stats().native_instructions > 0);
+    }
+
+    #[test]
+    fn breakpoints_interpret() {
+        let program = assemble(
+            "
+                in n
+            loop:
+                add n, #-1, n
+                jnz n, #loop
+                out n
+                halt
+            ",
+        )
+        .unwrap();
+        let mut jit = Jit::with_threshold(1);
+        let mut machine = machine(&program, &[1000]);
+        machine.add_breakpoint(2);
+        for _ in 0..3 {
+            assert_eq!(jit.run(&mut machine), Ok(StopReason::Breakpoint(2)));
+        }
+        assert_eq!(jit.stats(), JitStats::default());
+        machine.clear_breakpoints();
+        assert_eq!(jit.run_until_event(&mut machine), Ok(StopReason::Output(0)));
+        assert_eq!(jit.run_until_event(&mut machine), Ok(StopReason::Halted));
+        assert_eq!(machine.cost(), 1 + 2 * 1000 + 2);
+        assert!(jit.stats().native_instructions > 0);
+    }
+
+    #[test]
+    fn shared_across_machines() {
+        // Like day 19: one program run many times from fresh memory, which
+        // should compile each block once.
+        let program = parse_program(PRIME).unwrap();
+        let mut jit = Jit::with_threshold(4);
+        for n in 1000..1100 {
+            let (end, _) = compare(&mut jit, &program, &[n], &|_| ());
+            assert_eq!(end, Ok(StopReason::Halted));
+        }
+        let stats = jit.stats();
+        assert_eq!(stats.invalidated, 0);
+        assert!(stats.compiled < 10);
+
+        // A different program through the same Jit runs its own code, not
+        // whatever was compiled at the same addresses.
+        let deep_recursion = parse_program(DEEP_RECURSION).unwrap();
+        let (_, out) = compare(&mut jit, &deep_recursion, &[2, 3], &|_| ());
+        assert_eq!(out, [9]);
+    }
+
+    #[test]
+    fn shared_images_copied_on_write() {
+        // Walks the relative base along a table of zeros, which writes
+        // nothing at all.
+        let program = assemble(&format!(
+            "
+                arb #table
+            loop:
+                arb #1
+                jz rb+0, #loop
+                out rb+0
+                halt
+            table: .data {}7
+            ",
+            "0, ".repeat(200)
+        ))
+        .unwrap();
+        let image: Arc<[i64]> = program.into();
+        let mut jit = Jit::with_threshold(1);
+        for _ in 0..2 {
+            let mut machine = Intcode::shared(Arc::clone(&image), QueueIo::new());
+            assert_eq!(jit.run(&mut machine), Ok(StopReason::Halted));
+            assert_eq!(machine.into_io().pop_output(), Some(7));
+        }
+        assert!(jit.stats().native_instructions > 400);
+        let mut machine = Intcode::shared(Arc::clone(&image), QueueIo::new());
+        jit.run(&mut machine).unwrap();
+        assert!(machine.memory.is_shared());
+
+        // Writing regions hand the first write to the interpreter, which
+        // makes the copy, and carry on natively after that.
+        let image: Arc<[i64]> = parse_program(PRIME).unwrap().into();
+        let mut jit = Jit::with_threshold(1);
+        let mut io = QueueIo::new();
+        io.push_input(7919);
+        let mut machine = Intcode::shared(Arc::clone(&image), io);
+        assert_eq!(jit.run(&mut machine), Ok(StopReason::Halted));
+        assert_eq!(machine.io_mut().pop_output(), Some(1));
+        assert!(!machine.memory.is_shared());
+        assert!(jit.stats().native_instructions > 0);
+        assert_eq!(&image[..], &parse_program(PRIME).unwrap()[..]);
+    }
+
+    const BUDGET: u64 = 500;
+
+    // Mostly instructions that compiled code can run, with operands small
+    // enough to make jumps land in the program and addresses mostly in it.
+    fn instruction() -> impl Strategy<Value = Vec<i64>> {
+        let mode = prop_oneof![6 => Just(0), 4 => Just(1), 3 => Just(2), 1 => Just(3)];
+        let operand = prop_oneof![
+            10 => -3..40i64,
+            1 => prop::sample::select(vec![i64::MIN, i64::MAX, 1 << 40, 5_000]),
+        ];
+        (
+            prop::sample::select(vec![1, 1, 2, 5, 6, 7, 8, 9, 9, 4, 3, 99]),
+            [mode.clone(), mode.clone(), mode],
+            [operand.clone(), operand.clone(), operand],
+        )
+            .prop_map(|(opcode, modes, operands)| {
+                let count = match opcode {
+                    1 | 2 | 7 | 8 => 3,
+                    5 | 6 => 2,
+                    3 | 4 | 9 => 1,
+                    _ => 0,
+                };
+                let modes = modes.iter().rev().fold(0, |all, mode| all * 10 + mode);
+                let mut words = vec![opcode + 100 * modes];
+                words.extend_from_slice(&operands[..count]);
+                words
+            })
+    }
+
+    // Runs the JIT for `BUDGET` instructions or until it stops, then the
+    // interpreter up to the same cost: whatever happens, they should agree.
+    fn differential(program: &[i64], inputs: &[i64], policy: OverflowPolicy) {
+        let mut jit = Jit::with_threshold(1);
+        let mut compiled = machine(program, inputs);
+        compiled.set_overflow_policy(policy);
+        let compiled_end = loop {
+            match jit.advance(&mut compiled, true) {
+                Ok(None) if compiled.cost() < BUDGET => (),
+                end => break end,
+            }
+        };
+        let mut interpreted = machine(program, inputs);
+        interpreted.set_overflow_policy(policy);
+        let interpreted_end = loop {
+            match interpreted.advance(true) {
+                Ok(None) if interpreted.cost() < compiled.cost() => (),
+                // Errors and waiting for input cost nothing, so one may be due
+                // at the same cost.
+                Ok(None) if compiled_end != Ok(None) && interpreted.cost() == compiled.cost() => (),
+                end => break end,
+            }
+        };
+        assert_eq!(compiled_end, interpreted_end);
+        assert_eq!(compiled.cost(), interpreted.cost());
+        assert_eq!(compiled.snapshot(), interpreted.snapshot());
+        assert_eq!(outputs(&mut compiled), outputs(&mut interpreted));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(500))]
+
+        #[test]
+        fn matches_interpreter(
+            instructions in prop::collection::vec(instruction(), 1..16),
+            inputs in prop::collection::vec(-5..5i64, 0..4),
+            policy in prop::sample::select(vec![
+                OverflowPolicy::Error,
+                OverflowPolicy::Wrap,
+                OverflowPolicy::Saturate,
+            ]),
+        ) {
+            differential(&instructions.concat(), &inputs, policy);
+        }
+    }
+}
@@ -0,0 +1,1296 @@
+//! An Intcode virtual machine, as specified across the Advent of Code 2019
+//! puzzles: position, immediate and relative parameter modes, with IO routed
+//! through a caller-provided `IO` implementation.
+
+use coverage::Coverage;
+use memory::Memory;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+pub mod asm;
+#[cfg(feature = "tokio")]
+pub mod async_io;
+pub mod cluster;
+pub mod coverage;
+pub mod io;
+#[cfg(feature = "jit")]
+pub mod jit;
+mod memory;
+pub mod parse;
+
+pub trait IO {
+    fn input(&mut self) -> i64;
+    fn output(&mut self, v: i64);
+
+    /// Like `input`, but may report that nothing is available yet, in which
+    /// case `run_until_event` stops with `StopReason::NeedsInput` instead of
+    /// waiting.
+    fn try_input(&mut self) -> Option<i64> {
+        Some(self.input())
+    }
+}
+
+/// Lets a machine borrow its IO rather than own it, for callers that want the
+/// IO back (or want to look at it) once the machine is done.
+impl<T> IO for &mut T
+where
+    T: IO + ?Sized,
+{
+    fn input(&mut self) -> i64 {
+        (**self).input()
+    }
+
+    fn output(&mut self, v: i64) {
+        (**self).output(v)
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        (**self).try_input()
+    }
+}
+
+pub struct Intcode<T>
+where
+    T: IO,
+{
+    pc: usize,
+    memory: Memory,
+    relative_base: i64,
+    io: T,
+    cost_model: Option<CostModel>,
+    cost: u64,
+    overflow: OverflowPolicy,
+    input_default: Option<i64>,
+    breakpoints: HashSet<usize>,
+    custom: HashMap<i64, CustomOpcode>,
+    coverage: Option<Coverage>,
+    // Decoded instructions by pc, cleared whenever one of their words is
+    // written.
+    decoded: Vec<Option<RawWords>>,
+    // The breakpoint we last stopped on, so that running again steps over it
+    // rather than stopping in the same place forever.
+    resume_pc: Option<usize>,
+}
+
+/// Everything needed to put a machine back exactly where it was, minus its IO.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IntcodeState {
+    pub pc: usize,
+    pub relative_base: i64,
+    pub ram: Vec<i64>,
+    /// Isolated far addresses written outside of `ram`. On `restore`, any
+    /// inside `ram` are written into it instead.
+    pub sparse: BTreeMap<usize, i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunError {
+    InvalidOpcode {
+        pc: usize,
+        instruction: i64,
+    },
+    InvalidMode {
+        pc: usize,
+        instruction: i64,
+    },
+    NegativeAddress {
+        pc: usize,
+        address: i64,
+    },
+    ImmediateWrite {
+        pc: usize,
+    },
+    /// Only from the one-shot runners, whose inputs are all given up front.
+    InputExhausted {
+        pc: usize,
+    },
+    /// Only from `run_to_first_output`.
+    NoOutput {
+        pc: usize,
+    },
+    /// An add or multiply overflowed under `OverflowPolicy::Error`, or the
+    /// relative base (or an address relative to it) went past the range of
+    /// an `i64`, whatever the policy.
+    Overflow {
+        pc: usize,
+    },
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunError::InvalidOpcode { pc, instruction } => {
+                write!(f, "invalid opcode in {} at pc {}", instruction, pc)
+            }
+            RunError::InvalidMode { pc, instruction } => {
+                write!(f, "invalid parameter mode in {} at pc {}", instruction, pc)
+            }
+            RunError::NegativeAddress { pc, address } => {
+                write!(f, "negative address {} at pc {}", address, pc)
+            }
+            RunError::ImmediateWrite { pc } => {
+                write!(f, "write to an immediate parameter at pc {}", pc)
+            }
+            RunError::InputExhausted { pc } => {
+                write!(f, "ran out of input at pc {}", pc)
+            }
+            RunError::NoOutput { pc } => write!(f, "halted without output at pc {}", pc),
+            RunError::Overflow { pc } => write!(f, "arithmetic overflow at pc {}", pc),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+// The words of one instruction with its modes already split out. None of
+// this depends on the relative base, so it's cached per pc until something
+// writes over it.
+#[derive(Debug, Clone, Copy)]
+struct RawWords {
+    instruction: i64,
+    opcode: i64,
+    modes: [i64; 3],
+    params: [i64; 3],
+}
+
+impl RawWords {
+    fn new(instruction: i64, params: [i64; 3]) -> RawWords {
+        RawWords {
+            instruction,
+            opcode: instruction % 100,
+            modes: [
+                (instruction / 100) % 10,
+                (instruction / 1000) % 10,
+                (instruction / 10000) % 10,
+            ],
+            params,
+        }
+    }
+
+    fn param(&self, n: usize, pc: usize, relative_base: i64) -> Result<Parameter, RunError> {
+        let value = self.params[n];
+        let address = match self.modes[n] {
+            // position mode
+            0 => value,
+            // immediate mode
+            1 => return Ok(Parameter::Immediate { value }),
+            // relative mode
+            2 => value
+                .checked_add(relative_base)
+                .ok_or(RunError::Overflow { pc })?,
+            _ => {
+                return Err(RunError::InvalidMode {
+                    pc,
+                    instruction: self.instruction,
+                })
+            }
+        };
+        match usize::try_from(address) {
+            Ok(address) => Ok(Parameter::Indirect { address }),
+            Err(_) => Err(RunError::NegativeAddress { pc, address }),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Parameter {
+    Indirect { address: usize },
+    Immediate { value: i64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Add,
+    Multiply,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    RelativeBaseOffset,
+    Halt,
+    /// An opcode added with `Intcode::add_opcode`.
+    Custom(i64),
+}
+
+type Handler = Box<dyn FnMut(&[i64]) -> i64 + Send>;
+
+// The shape and behaviour of an opcode added by the embedder.
+struct CustomOpcode {
+    inputs: usize,
+    writes: bool,
+    handler: Handler,
+}
+
+/// What a single call to `Intcode::step` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub pc: usize,
+    pub opcode: Opcode,
+    pub next_pc: usize,
+}
+
+/// The words just below the relative base, read as a call frame. Compiled
+/// Intcode programs (including the official puzzle programs) allocate a frame
+/// by bumping the relative base on entry and address it as `rb-size..rb-1`,
+/// with the return address in the lowest word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub base: i64,
+    pub words: Vec<i64>,
+}
+
+impl Frame {
+    /// The word at `rb + offset`, for negative `offset` within the frame.
+    pub fn get(&self, offset: i64) -> Option<i64> {
+        let index = self.words.len() as i64 + offset;
+        if offset < 0 && index >= 0 {
+            Some(self.words[index as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn return_address(&self) -> Option<i64> {
+        self.words.first().cloned()
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let size = self.words.len() as i64;
+        for (i, word) in self.words.iter().enumerate() {
+            let offset = i as i64 - size;
+            writeln!(f, "rb{:<4} [{:>5}] {}", offset, self.base + offset, word)?;
+        }
+        Ok(())
+    }
+}
+
+/// What add and multiply do when the result doesn't fit in an `i64`. The
+/// default is to stop with `RunError::Overflow`, so that a wrapped value can't
+/// quietly turn into a wrong answer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OverflowPolicy {
+    #[default]
+    Error,
+    Saturate,
+    Wrap,
+}
+
+/// Per-opcode weights for tallying up the "cost" of a run, so that different
+/// programs (or inputs) for the same job can be compared independently of
+/// wall-clock time. The default weighs every instruction as 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CostModel {
+    pub add: u64,
+    pub multiply: u64,
+    pub input: u64,
+    pub output: u64,
+    pub jump_if_true: u64,
+    pub jump_if_false: u64,
+    pub less_than: u64,
+    pub equals: u64,
+    pub relative_base_offset: u64,
+    pub halt: u64,
+    /// Every opcode added with `Intcode::add_opcode`.
+    pub custom: u64,
+}
+
+impl CostModel {
+    pub fn weight(&self, opcode: Opcode) -> u64 {
+        match opcode {
+            Opcode::Add => self.add,
+            Opcode::Multiply => self.multiply,
+            Opcode::Input => self.input,
+            Opcode::Output => self.output,
+            Opcode::JumpIfTrue => self.jump_if_true,
+            Opcode::JumpIfFalse => self.jump_if_false,
+            Opcode::LessThan => self.less_than,
+            Opcode::Equals => self.equals,
+            Opcode::RelativeBaseOffset => self.relative_base_offset,
+            Opcode::Halt => self.halt,
+            Opcode::Custom(_) => self.custom,
+        }
+    }
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel {
+            add: 1,
+            multiply: 1,
+            input: 1,
+            output: 1,
+            jump_if_true: 1,
+            jump_if_false: 1,
+            less_than: 1,
+            equals: 1,
+            relative_base_offset: 1,
+            halt: 1,
+            custom: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Halted,
+    NeedsInput,
+    Output(i64),
+    /// About to execute the instruction at a breakpoint.
+    Breakpoint(usize),
+    /// Just gave the program the input default, having found no input
+    /// waiting. Only with `Intcode::set_input_default`.
+    DefaultInput,
+}
+
+// What an executed instruction did, beyond moving the pc, that
+// `run_until_event` reports.
+enum Effect {
+    None,
+    Output(i64),
+    DefaultInput,
+}
+
+enum Instruction {
+    Add {
+        op1: Parameter,
+        op2: Parameter,
+        dest: Parameter,
+    },
+    Multiply {
+        op1: Parameter,
+        op2: Parameter,
+        dest: Parameter,
+    },
+    Input {
+        dest: Parameter,
+    },
+    Output {
+        from: Parameter,
+    },
+    JumpIfTrue {
+        condition: Parameter,
+        target: Parameter,
+    },
+    JumpIfFalse {
+        condition: Parameter,
+        target: Parameter,
+    },
+    LessThan {
+        op1: Parameter,
+        op2: Parameter,
+        dest: Parameter,
+    },
+    Equals {
+        op1: Parameter,
+        op2: Parameter,
+        dest: Parameter,
+    },
+    RelativeBaseOffset {
+        incr: Parameter,
+    },
+    Halt,
+    Custom {
+        opcode: i64,
+        inputs: usize,
+        writes: bool,
+        // Inputs first, then the destination if the opcode writes.
+        params: [Parameter; 3],
+    },
+}
+
+impl Instruction {
+    // How many words the instruction occupies, operands included.
+    fn len(&self) -> usize {
+        match self {
+            Instruction::Add { .. }
+            | Instruction::Multiply { .. }
+            | Instruction::LessThan { .. }
+            | Instruction::Equals { .. } => 4,
+            Instruction::JumpIfTrue { .. } | Instruction::JumpIfFalse { .. } => 3,
+            Instruction::Input { .. }
+            | Instruction::Output { .. }
+            | Instruction::RelativeBaseOffset { .. } => 2,
+            Instruction::Halt => 1,
+            Instruction::Custom { inputs, writes, .. } => 1 + inputs + *writes as usize,
+        }
+    }
+
+    fn opcode(&self) -> Opcode {
+        match self {
+            Instruction::Add { .. } => Opcode::Add,
+            Instruction::Multiply { .. } => Opcode::Multiply,
+            Instruction::Input { .. } => Opcode::Input,
+            Instruction::Output { .. } => Opcode::Output,
+            Instruction::JumpIfTrue { .. } => Opcode::JumpIfTrue,
+            Instruction::JumpIfFalse { .. } => Opcode::JumpIfFalse,
+            Instruction::LessThan { .. } => Opcode::LessThan,
+            Instruction::Equals { .. } => Opcode::Equals,
+            Instruction::RelativeBaseOffset { .. } => Opcode::RelativeBaseOffset,
+            Instruction::Halt => Opcode::Halt,
+            Instruction::Custom { opcode, .. } => Opcode::Custom(*opcode),
+        }
+    }
+}
+
+impl<T> Intcode<T>
+where
+    T: IO,
+{
+    /// `io` can be owned outright, or passed as `&mut io` to keep hold of it.
+    pub fn new(ram: Vec<i64>, io: T) -> Intcode<T> {
+        Intcode::with_memory(Memory::new(ram), io)
+    }
+
+    /// Like `new`, but shares `program` with any other machines started from
+    /// it. Each machine only takes a copy of its own once it first writes to
+    /// the program's memory.
+    pub fn shared(program: Arc<[i64]>, io: T) -> Intcode<T> {
+        Intcode::with_memory(Memory::shared(program), io)
+    }
+
+    fn with_memory(memory: Memory, io: T) -> Intcode<T> {
+        Intcode {
+            pc: 0,
+            memory,
+            relative_base: 0,
+            io,
+            cost_model: None,
+            cost: 0,
+            overflow: OverflowPolicy::default(),
+            input_default: None,
+            breakpoints: HashSet::new(),
+            custom: HashMap::new(),
+            coverage: None,
+            decoded: Vec::new(),
+            resume_pc: None,
+        }
+    }
+
+    /// Starts accumulating cost under `model` from this point on.
+    pub fn set_cost_model(&mut self, model: CostModel) {
+        self.cost_model = Some(model);
+    }
+
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.overflow = policy;
+    }
+
+    /// With `Some(v)`, an input instruction that finds the IO has nothing
+    /// waiting (per `IO::try_input`) reads `v` rather than blocking, as day
+    /// 23's NICs expect of their `-1`. `run_until_event` then stops with
+    /// `StopReason::DefaultInput` so that polling drivers get a chance to
+    /// move on; `run` carries straight on.
+    pub fn set_input_default(&mut self, default: Option<i64>) {
+        self.input_default = default;
+    }
+
+    /// Total cost of the instructions executed while a cost model was set.
+    pub fn cost(&self) -> u64 {
+        self.cost
+    }
+
+    /// Makes `run` and `run_until_event` stop before executing the
+    /// instruction at `pc`.
+    pub fn add_breakpoint(&mut self, pc: usize) {
+        self.breakpoints.insert(pc);
+    }
+
+    /// Returns whether there was a breakpoint at `pc`.
+    pub fn remove_breakpoint(&mut self, pc: usize) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Starts recording which words are executed, and any writes into them,
+    /// from scratch if it was already on.
+    ///
+    /// This is also what turns on self-modification reporting: a write only
+    /// counts as one if it lands on a word executed since this call, so code
+    /// rewritten before tracking started (or with tracking off) goes unseen.
+    pub fn track_coverage(&mut self) {
+        self.coverage = Some(Coverage::new(self.memory.dense().len()));
+    }
+
+    /// What has been executed since `track_coverage`, if it was called.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Teaches the machine a new opcode, e.g. for experimenting with extended
+    /// Intcode. The instruction takes `inputs` operands, whose values are
+    /// passed to `handler`, followed by one more operand that the result is
+    /// written to if `writes` is set. Operands use the usual parameter modes.
+    /// Built-in opcodes can't be replaced.
+    ///
+    /// Panics if the opcode is built in, isn't in 1..100, or would need more
+    /// than three operands.
+    pub fn add_opcode<F>(&mut self, opcode: i64, inputs: usize, writes: bool, handler: F)
+    where
+        F: FnMut(&[i64]) -> i64 + Send + 'static,
+    {
+        assert!(
+            (1..100).contains(&opcode) && !(1..=9).contains(&opcode) && opcode != 99,
+            "opcode {} is built in or out of range",
+            opcode
+        );
+        assert!(
+            inputs + writes as usize <= 3,
+            "opcodes take at most three operands"
+        );
+        self.custom.insert(
+            opcode,
+            CustomOpcode {
+                inputs,
+                writes,
+                handler: Box::new(handler),
+            },
+        );
+        // Anything already decoded as an invalid opcode may be valid now.
+        self.decoded.clear();
+    }
+
+    /// Runs until the machine halts or hits a breakpoint. Running again from a
+    /// breakpoint carries on past it.
+    pub fn run(&mut self) -> Result<StopReason, RunError> {
+        loop {
+            if let Some(reason) = self.advance(false)? {
+                return Ok(reason);
+            }
+        }
+    }
+
+    /// Runs until the machine halts, hits a breakpoint, produces an output
+    /// (which is also passed on to the IO as usual), or asks for input the IO
+    /// doesn't have yet. In the last case the input instruction is retried on
+    /// the next call.
+    pub fn run_until_event(&mut self) -> Result<StopReason, RunError> {
+        loop {
+            if let Some(reason) = self.advance(true)? {
+                return Ok(reason);
+            }
+        }
+    }
+
+    // One instruction's worth of `run_until_event` (when polling) or `run`,
+    // returning why to stop, if it's time to.
+    fn advance(&mut self, poll: bool) -> Result<Option<StopReason>, RunError> {
+        if self.breakpoint_hit() {
+            return Ok(Some(StopReason::Breakpoint(self.pc)));
+        }
+        Ok(match self.execute(poll)? {
+            None => Some(StopReason::NeedsInput),
+            Some((step, _)) if step.opcode == Opcode::Halt => Some(StopReason::Halted),
+            Some((_, Effect::Output(v))) if poll => Some(StopReason::Output(v)),
+            Some((_, Effect::DefaultInput)) if poll => Some(StopReason::DefaultInput),
+            Some(_) => None,
+        })
+    }
+
+    /// Executes exactly one instruction. Stepping a halted machine leaves it
+    /// sitting on the halt instruction.
+    pub fn step(&mut self) -> Result<Step, RunError> {
+        match self.execute(false)? {
+            Some((step, _)) => Ok(step),
+            None => unreachable!("blocking input always produces a value"),
+        }
+    }
+
+    // Executes one instruction, also returning its effect. When polling, an
+    // input instruction with no input available (and no default) is left
+    // unexecuted and None is returned.
+    fn execute(&mut self, poll: bool) -> Result<Option<(Step, Effect)>, RunError> {
+        let pc = self.pc;
+        let mut effect = Effect::None;
+        let instruction = self.decode()?;
+        let opcode = instruction.opcode();
+        let len = instruction.len();
+        match instruction {
+            Instruction::Add { op1, op2, dest } => {
+                let op1 = self.read(op1);
+                let op2 = self.read(op2);
+                let sum = match self.overflow {
+                    OverflowPolicy::Error => op1.checked_add(op2),
+                    OverflowPolicy::Saturate => Some(op1.saturating_add(op2)),
+                    OverflowPolicy::Wrap => Some(op1.wrapping_add(op2)),
+                };
+                let sum = sum.ok_or(RunError::Overflow { pc: self.pc })?;
+                self.write(dest, sum)?;
+                self.pc += 4;
+            }
+            Instruction::Multiply { op1, op2, dest } => {
+                let op1 = self.read(op1);
+                let op2 = self.read(op2);
+                let product = match self.overflow {
+                    OverflowPolicy::Error => op1.checked_mul(op2),
+                    OverflowPolicy::Saturate => Some(op1.saturating_mul(op2)),
+                    OverflowPolicy::Wrap => Some(op1.wrapping_mul(op2)),
+                };
+                let product = product.ok_or(RunError::Overflow { pc: self.pc })?;
+                self.write(dest, product)?;
+                self.pc += 4;
+            }
+            Instruction::Input { dest } => {
+                let value = match self.input_default {
+                    Some(default) => self.io.try_input().unwrap_or_else(|| {
+                        effect = Effect::DefaultInput;
+                        default
+                    }),
+                    None if poll => match self.io.try_input() {
+                        Some(value) => value,
+                        None => return Ok(None),
+                    },
+                    None => self.io.input(),
+                };
+                self.write(dest, value)?;
+                self.pc += 2;
+            }
+            Instruction::Output { from } => {
+                let value = self.read(from);
+                self.io.output(value);
+                effect = Effect::Output(value);
+                self.pc += 2;
+            }
+            Instruction::JumpIfTrue { condition, target } => {
+                if self.read(condition) != 0 {
+                    let target = self.read(target);
+                    self.pc = self.address(target)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            Instruction::JumpIfFalse { condition, target } => {
+                if self.read(condition) == 0 {
+                    let target = self.read(target);
+                    self.pc = self.address(target)?;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            Instruction::LessThan { op1, op2, dest } => {
+                if self.read(op1) < self.read(op2) {
+                    self.write(dest, 1)?;
+                } else {
+                    self.write(dest, 0)?;
+                };
+                self.pc += 4;
+            }
+            Instruction::Equals { op1, op2, dest } => {
+                if self.read(op1) == self.read(op2) {
+                    self.write(dest, 1)?;
+                } else {
+                    self.write(dest, 0)?;
+                };
+                self.pc += 4;
+            }
+            Instruction::RelativeBaseOffset { incr } => {
+                let value = self.read(incr);
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(value)
+                    .ok_or(RunError::Overflow { pc: self.pc })?;
+                self.pc += 2;
+            }
+            Instruction::Halt => (),
+            Instruction::Custom {
+                opcode,
+                inputs,
+                writes,
+                params,
+            } => {
+                let mut values = [0; 3];
+                for (value, &param) in values.iter_mut().zip(&params[..inputs]) {
+                    *value = self.read(param);
+                }
+                let handler = &mut self.custom.get_mut(&opcode).unwrap().handler;
+                let result = handler(&values[..inputs]);
+                if writes {
+                    self.write(params[inputs], result)?;
+                }
+                self.pc += 1 + inputs + writes as usize;
+            }
+        }
+
+        self.resume_pc = None;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.mark(pc, len);
+        }
+        if let Some(model) = &self.cost_model {
+            self.cost += model.weight(opcode);
+        }
+
+        let step = Step {
+            pc,
+            opcode,
+            next_pc: self.pc,
+        };
+        Ok(Some((step, effect)))
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    /// The `size` words below the relative base; see `Frame`. Addresses below
+    /// zero read as zero.
+    pub fn frame(&self, size: usize) -> Frame {
+        let words = (1..=size as i64)
+            .rev()
+            .map(|offset| {
+                match self
+                    .relative_base
+                    .checked_sub(offset)
+                    .and_then(|address| usize::try_from(address).ok())
+                {
+                    Some(address) => self.memory.get(address),
+                    None => 0,
+                }
+            })
+            .collect();
+        Frame {
+            base: self.relative_base,
+            words,
+        }
+    }
+
+    pub fn io(&self) -> &T {
+        &self.io
+    }
+
+    pub fn io_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    pub fn into_io(self) -> T {
+        self.io
+    }
+
+    /// The dense part of memory: the program and anything grown contiguously
+    /// off the end of it. Isolated far writes aren't included; see `snapshot`.
+    pub fn ram(&self) -> &[i64] {
+        self.memory.dense()
+    }
+
+    pub fn snapshot(&self) -> IntcodeState {
+        IntcodeState {
+            pc: self.pc,
+            relative_base: self.relative_base,
+            ram: self.memory.dense().to_vec(),
+            sparse: self.memory.sparse(),
+        }
+    }
+
+    pub fn restore(&mut self, state: IntcodeState) {
+        self.pc = state.pc;
+        self.relative_base = state.relative_base;
+        self.memory = Memory::from_parts(state.ram, state.sparse);
+        self.decoded.clear();
+        self.resume_pc = None;
+    }
+
+    fn into_ram(self) -> Vec<i64> {
+        self.memory.into_dense()
+    }
+
+    fn breakpoint_hit(&mut self) -> bool {
+        if self.resume_pc == Some(self.pc) || !self.breakpoints.contains(&self.pc) {
+            return false;
+        }
+        self.resume_pc = Some(self.pc);
+        true
+    }
+
+    fn address(&self, value: i64) -> Result<usize, RunError> {
+        usize::try_from(value).map_err(|_| RunError::NegativeAddress {
+            pc: self.pc,
+            address: value,
+        })
+    }
+
+    fn fetch(&mut self) -> RawWords {
+        let pc = self.pc;
+        if let Some(Some(raw)) = self.decoded.get(pc) {
+            return *raw;
+        }
+        let raw = RawWords::new(
+            self.memory.get(pc),
+            [
+                self.memory.get(pc + 1),
+                self.memory.get(pc + 2),
+                self.memory.get(pc + 3),
+            ],
+        );
+        // Only cache inside dense memory, so a wild jump can't balloon the
+        // cache.
+        if pc < self.memory.dense().len() {
+            if self.decoded.len() <= pc {
+                self.decoded.resize(pc + 1, None);
+            }
+            self.decoded[pc] = Some(raw);
+        }
+        raw
+    }
+
+    fn decode(&mut self) -> Result<Instruction, RunError> {
+        let raw = self.fetch();
+        let (pc, relative_base) = (self.pc, self.relative_base);
+        let param = |n| raw.param(n, pc, relative_base);
+        Ok(match raw.opcode {
+            1 => Instruction::Add {
+                op1: param(0)?,
+                op2: param(1)?,
+                dest: param(2)?,
+            },
+            2 => Instruction::Multiply {
+                op1: param(0)?,
+                op2: param(1)?,
+                dest: param(2)?,
+            },
+            3 => Instruction::Input { dest: param(0)? },
+            4 => Instruction::Output { from: param(0)? },
+            5 => Instruction::JumpIfTrue {
+                condition: param(0)?,
+                target: param(1)?,
+            },
+            6 => Instruction::JumpIfFalse {
+                condition: param(0)?,
+                target: param(1)?,
+            },
+            7 => Instruction::LessThan {
+                op1: param(0)?,
+                op2: param(1)?,
+                dest: param(2)?,
+            },
+            8 => Instruction::Equals {
+                op1: param(0)?,
+                op2: param(1)?,
+                dest: param(2)?,
+            },
+            9 => Instruction::RelativeBaseOffset { incr: param(0)? },
+            99 => Instruction::Halt,
+            opcode if self.custom.contains_key(&opcode) => {
+                let custom = &self.custom[&opcode];
+                let mut params = [Parameter::Immediate { value: 0 }; 3];
+                for (n, param) in params
+                    .iter_mut()
+                    .enumerate()
+                    .take(custom.inputs + custom.writes as usize)
+                {
+                    *param = raw.param(n, pc, relative_base)?;
+                }
+                Instruction::Custom {
+                    opcode,
+                    inputs: custom.inputs,
+                    writes: custom.writes,
+                    params,
+                }
+            }
+            _ => {
+                return Err(RunError::InvalidOpcode {
+                    pc: self.pc,
+                    instruction: raw.instruction,
+                })
+            }
+        })
+    }
+
+    fn read(&mut self, param: Parameter) -> i64 {
+        match param {
+            Parameter::Indirect { address } => self.memory.get(address),
+            Parameter::Immediate { value } => value,
+        }
+    }
+
+    fn write(&mut self, param: Parameter, value: i64) -> Result<(), RunError> {
+        match param {
+            Parameter::Indirect { address } => {
+                self.memory.set(address, value);
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.written(self.pc, address);
+                }
+                self.forget_decoded(address);
+                Ok(())
+            }
+            Parameter::Immediate { .. } => Err(RunError::ImmediateWrite { pc: self.pc }),
+        }
+    }
+
+    // Any instruction the word at `address` belongs to has to be decoded
+    // again.
+    fn forget_decoded(&mut self, address: usize) {
+        let end = (address + 1).min(self.decoded.len());
+        for entry in self
+            .decoded
+            .iter_mut()
+            .take(end)
+            .skip(address.saturating_sub(3))
+        {
+            *entry = None;
+        }
+    }
+}
+
+/// Runs `program` on `inputs` until it produces its first output.
+pub fn run_to_first_output(program: &[i64], inputs: &[i64]) -> Result<i64, RunError> {
+    let mut machine = Intcode::new(program.to_vec(), queued(inputs));
+    loop {
+        match machine.run_until_event()? {
+            StopReason::Output(v) => return Ok(v),
+            StopReason::Halted => return Err(RunError::NoOutput { pc: machine.pc() }),
+            StopReason::NeedsInput => return Err(RunError::InputExhausted { pc: machine.pc() }),
+            StopReason::Breakpoint(_) | StopReason::DefaultInput => (),
+        }
+    }
+}
+
+/// Runs `program` on `inputs` until it halts, returning everything it output.
+pub fn run_collect(program: &[i64], inputs: &[i64]) -> Result<Vec<i64>, RunError> {
+    run_program(program, inputs).map(|finished| finished.outputs)
+}
+
+/// What a program left behind when it halted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finished {
+    pub outputs: Vec<i64>,
+    /// The dense part of memory, as with `Intcode::ram`.
+    pub ram: Vec<i64>,
+}
+
+/// Like `run_collect`, but also hands back memory as the program left it.
+pub fn run_program(program: &[i64], inputs: &[i64]) -> Result<Finished, RunError> {
+    let mut machine = Intcode::new(program.to_vec(), queued(inputs));
+    loop {
+        match machine.run_until_event()? {
+            StopReason::Halted => break,
+            StopReason::NeedsInput => return Err(RunError::InputExhausted { pc: machine.pc() }),
+            StopReason::Output(_) | StopReason::Breakpoint(_) | StopReason::DefaultInput => (),
+        }
+    }
+    let ram = machine.ram().to_vec();
+    let mut io = machine.into_io();
+    Ok(Finished {
+        outputs: std::iter::from_fn(|| io.pop_output()).collect(),
+        ram,
+    })
+}
+
+fn queued(inputs: &[i64]) -> io::QueueIo {
+    let mut io = io::QueueIo::new();
+    for &v in inputs {
+        io.push_input(v);
+    }
+    io
+}
+
+/// Hands out machines loaded with the same program, reusing the RAM of
+/// machines returned via `recycle` so that searches running the program many
+/// times stop allocating once the pool has warmed up.
+pub struct MachinePool {
+    program: Vec<i64>,
+    buffers: Vec<Vec<i64>>,
+}
+
+impl MachinePool {
+    pub fn new(program: Vec<i64>) -> MachinePool {
+        MachinePool {
+            program,
+            buffers: Vec::new(),
+        }
+    }
+
+    pub fn machine<T>(&mut self, io: T) -> Intcode<T>
+    where
+        T: IO,
+    {
+        let mut ram = self.buffers.pop().unwrap_or_default();
+        ram.clear();
+        ram.extend_from_slice(&self.program);
+        Intcode::new(ram, io)
+    }
+
+    pub fn recycle<T>(&mut self, machine: Intcode<T>)
+    where
+        T: IO,
+    {
+        self.buffers.push(machine.into_ram());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::QueueIo;
+
+    fn run(program: &[i64]) -> Vec<i64> {
+        let mut io = (std::iter::empty(), Vec::new());
+        Intcode::new(program.to_vec(), &mut io).run().unwrap();
+        io.1
+    }
+
+    #[test]
+    fn frame_below_relative_base() {
+        let mut io = (std::iter::empty(), Vec::new());
+        // Allocate a three-word frame at 20 and fill it in.
+        let program = vec![109, 23, 21101, 7, 0, -3, 21101, 8, 0, -1, 99];
+        let mut machine = Intcode::new(program, &mut io);
+        machine.run().unwrap();
+        let frame = machine.frame(3);
+        assert_eq!(frame.words, [7, 0, 8]);
+        assert_eq!(frame.return_address(), Some(7));
+        assert_eq!(frame.get(-1), Some(8));
+        assert_eq!(frame.get(0), None);
+        assert_eq!(frame.get(-4), None);
+    }
+
+    #[test]
+    fn owned_io() {
+        let mut machines: Vec<_> = (0..3)
+            .map(|i| Intcode::new(vec![104, i, 99], (std::iter::empty(), Vec::new())))
+            .collect();
+        for machine in &mut machines {
+            machine.run().unwrap();
+        }
+        let outputs: Vec<_> = machines.iter().map(|m| m.io().1.clone()).collect();
+        assert_eq!(outputs, [[0], [1], [2]]);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut io = (std::iter::empty(), Vec::new());
+        let mut machine = Intcode::new(vec![104, 1, 104, 2, 104, 3, 99], &mut io);
+        machine.add_breakpoint(2);
+        machine.add_breakpoint(6);
+        assert_eq!(machine.run(), Ok(StopReason::Breakpoint(2)));
+        assert_eq!(machine.io().1, [1]);
+        assert_eq!(machine.run(), Ok(StopReason::Breakpoint(6)));
+        assert!(machine.remove_breakpoint(2));
+        assert_eq!(machine.run(), Ok(StopReason::Halted));
+        assert_eq!(io.1, [1, 2, 3]);
+    }
+
+    #[test]
+    fn input_default() {
+        // Echoes two inputs.
+        let program = vec![3, 9, 4, 9, 3, 10, 4, 10, 99, 0, 0];
+        let mut io = QueueIo::new();
+        io.push_input(5);
+        let mut machine = Intcode::new(program.clone(), io);
+        machine.set_input_default(Some(-1));
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Output(5)));
+        assert_eq!(machine.run_until_event(), Ok(StopReason::DefaultInput));
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Output(-1)));
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Halted));
+
+        // QueueIo would panic when blocking for input it doesn't have.
+        let mut machine = Intcode::new(program, QueueIo::new());
+        machine.set_input_default(Some(0));
+        assert_eq!(machine.run(), Ok(StopReason::Halted));
+        let io = machine.io_mut();
+        assert_eq!((io.pop_output(), io.pop_output()), (Some(0), Some(0)));
+    }
+
+    #[test]
+    fn shared_program_copied_on_write() {
+        // Outputs its input doubled, using the input word as scratch space.
+        let program: Arc<[i64]> = vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0].into();
+        let mut outputs = Vec::new();
+        for input in 1..=3 {
+            let mut io = QueueIo::new();
+            io.push_input(input);
+            let mut machine = Intcode::shared(Arc::clone(&program), io);
+            machine.run().unwrap();
+            assert_eq!(machine.ram()[9], 2 * input);
+            outputs.push(machine.into_io().pop_output());
+        }
+        assert_eq!(outputs, [Some(2), Some(4), Some(6)]);
+        assert_eq!(program[9], 0);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        // Doubles a counter into a growing stack, and keeps the latest count
+        // far off in sparse memory.
+        let program = crate::asm::assemble(
+            "
+                arb #500
+            loop:
+                add n, #1, n
+                arb #1
+                mul n, #2, rb+0
+                add n, #0, 1000000
+                out rb+0
+                lt n, #6, t
+                jnz t, #loop
+                halt
+            ",
+        )
+        .unwrap();
+        let rest = |machine: &mut Intcode<QueueIo>| {
+            let mut outputs = Vec::new();
+            loop {
+                match machine.run_until_event().unwrap() {
+                    StopReason::Output(v) => outputs.push(v),
+                    StopReason::Halted => return outputs,
+                    stop => panic!("unexpected {:?}", stop),
+                }
+            }
+        };
+
+        let mut machine = Intcode::new(program, QueueIo::new());
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Output(2)));
+        assert_eq!(machine.run_until_event(), Ok(StopReason::Output(4)));
+        let saved = machine.snapshot();
+        assert_eq!(saved.relative_base, 502);
+        assert_eq!(saved.ram[501..503], [2, 4]);
+        assert_eq!(saved.sparse.get(&1_000_000), Some(&2));
+
+        let outputs = rest(&mut machine);
+        assert_eq!(outputs, [6, 8, 10, 12]);
+        let finished = machine.snapshot();
+        assert_ne!(finished, saved);
+
+        machine.restore(saved.clone());
+        assert_eq!(machine.snapshot(), saved);
+        assert_eq!(rest(&mut machine), outputs);
+        assert_eq!(machine.snapshot(), finished);
+
+        // Into a machine that has never run, too.
+        let mut fresh = Intcode::new(vec![99], QueueIo::new());
+        fresh.restore(saved);
+        assert_eq!(rest(&mut fresh), outputs);
+        assert_eq!(fresh.snapshot(), finished);
+    }
+
+    #[test]
+    fn one_shot_runners() {
+        // Outputs its input, then its input plus one.
+        let program = [3, 11, 4, 11, 1001, 11, 1, 11, 4, 11, 99, 0];
+        assert_eq!(run_to_first_output(&program, &[7]), Ok(7));
+        assert_eq!(run_collect(&program, &[7]), Ok(vec![7, 8]));
+        assert_eq!(
+            run_collect(&program, &[]),
+            Err(RunError::InputExhausted { pc: 0 })
+        );
+        assert_eq!(run_program(&program, &[7]).unwrap().ram[11], 8);
+        assert_eq!(
+            run_to_first_output(&[99], &[]),
+            Err(RunError::NoOutput { pc: 0 })
+        );
+    }
+
+    #[test]
+    fn self_modifying_code_is_redecoded() {
+        let program = crate::asm::assemble(
+            "
+                out #1          ; the second pass sees the rewritten operand
+                add #5, #0, 1
+                add c, #1, c
+                lt c, #2, t
+                jnz t, #0
+                halt
+            ",
+        )
+        .unwrap();
+        assert_eq!(run(&program), [1, 5]);
+    }
+
+    #[test]
+    fn custom_opcodes() {
+        let mut machine = Intcode::new(
+            vec![142, 7, 20, 50, 21, 4, 20, 50, 21, 4, 21, 99],
+            (std::iter::empty::<i64>(), Vec::new()),
+        );
+        // 42 squares its operand into the destination; 50 counts up.
+        machine.add_opcode(42, 1, true, |values| values[0] * values[0]);
+        let mut counter = 0;
+        machine.add_opcode(50, 0, true, move |_| {
+            counter += 1;
+            counter
+        });
+        machine.set_cost_model(CostModel {
+            custom: 10,
+            ..CostModel::default()
+        });
+        assert_eq!(machine.run(), Ok(StopReason::Halted));
+        assert_eq!(machine.cost(), 3 * 10 + 3);
+        assert_eq!(machine.io().1, [49, 2]);
+
+        let mut io = (std::iter::empty(), Vec::new());
+        assert_eq!(
+            Intcode::new(vec![42, 0, 0, 99], &mut io).run(),
+            Err(RunError::InvalidOpcode {
+                pc: 0,
+                instruction: 42
+            })
+        );
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let program = vec![1102, i64::MAX, 2, 9, 1101, i64::MIN, -1, 10, 99, 0, 0];
+        let run_with = |policy| {
+            let mut io = (std::iter::empty(), Vec::new());
+            let mut machine = Intcode::new(program.clone(), &mut io);
+            machine.set_overflow_policy(policy);
+            machine.run().map(|_| machine.ram()[9..11].to_vec())
+        };
+        assert_eq!(
+            run_with(OverflowPolicy::Error),
+            Err(RunError::Overflow { pc: 0 })
+        );
+        assert_eq!(
+            run_with(OverflowPolicy::Saturate),
+            Ok(vec![i64::MAX, i64::MIN])
+        );
+        assert_eq!(run_with(OverflowPolicy::Wrap), Ok(vec![-2, i64::MAX]));
+    }
+
+    #[test]
+    fn relative_base_overflow() {
+        let mut io = (std::iter::empty(), Vec::new());
+        assert_eq!(
+            Intcode::new(vec![109, i64::MAX, 109, 1, 99], &mut io).run(),
+            Err(RunError::Overflow { pc: 2 })
+        );
+        assert_eq!(
+            Intcode::new(vec![109, i64::MAX, 204, 1, 99], &mut io).run(),
+            Err(RunError::Overflow { pc: 2 })
+        );
+    }
+
+    #[test]
+    fn day9_quine() {
+        let program = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        assert_eq!(run(&program), program);
+    }
+
+    #[test]
+    fn day9_sixteen_digit_product() {
+        let outputs = run(&[1102, 34_915_192, 34_915_192, 7, 4, 7, 99, 0]);
+        assert_eq!(outputs, [1_219_070_632_396_864]);
+        assert_eq!(outputs[0].to_string().len(), 16);
+    }
+
+    #[test]
+    fn day9_large_number() {
+        let outputs = run(&[104, 1_125_899_906_842_624, 99]);
+        assert_eq!(outputs, [1_125_899_906_842_624]);
+    }
+}
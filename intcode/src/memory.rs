@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+// Writes this far past the end of dense memory go to the sparse map instead of
+// growing the Vec, so that one far-flung address doesn't cost gigabytes of
+// zeroes.
+const MAX_DENSE_GROWTH: usize = 1 << 16;
+
+// Dense memory grows a page at a time: just past the address written, rounded
+// up, so the Vec is never much bigger than the highest address in use.
+const PAGE_SIZE: usize = 1 << 10;
+
+// The dense region starts out as a program image that may be shared with
+// other machines, and is only copied the first time something writes to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Dense {
+    Shared(Arc<[i64]>),
+    Owned(Vec<i64>),
+}
+
+impl Dense {
+    fn to_mut(&mut self) -> &mut Vec<i64> {
+        if let Dense::Shared(image) = self {
+            *self = Dense::Owned(image.to_vec());
+        }
+        match self {
+            Dense::Owned(dense) => dense,
+            Dense::Shared(_) => unreachable!("just copied"),
+        }
+    }
+}
+
+impl Default for Dense {
+    fn default() -> Self {
+        Dense::Owned(Vec::new())
+    }
+}
+
+/// Intcode memory: a dense Vec holding the program and whatever grows
+/// contiguously off the end of it, plus a sparse map for isolated far
+/// addresses. Every address that was never written reads as zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Memory {
+    dense: Dense,
+    sparse: HashMap<usize, i64>,
+}
+
+impl Memory {
+    pub(crate) fn new(dense: Vec<i64>) -> Memory {
+        Memory {
+            dense: Dense::Owned(dense),
+            sparse: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn shared(image: Arc<[i64]>) -> Memory {
+        Memory {
+            dense: Dense::Shared(image),
+            sparse: HashMap::new(),
+        }
+    }
+
+    /// Sparse entries for addresses inside `dense` are written into it, so
+    /// that every address has one place to live.
+    pub(crate) fn from_parts(mut dense: Vec<i64>, mut sparse: BTreeMap<usize, i64>) -> Memory {
+        let len = dense.len();
+        let far = sparse.split_off(&len);
+        for (address, value) in sparse {
+            dense[address] = value;
+        }
+        Memory {
+            dense: Dense::Owned(dense),
+            sparse: far.into_iter().collect(),
+        }
+    }
+
+    pub(crate) fn get(&self, address: usize) -> i64 {
+        match self.dense().get(address) {
+            Some(&value) => value,
+            None => self.sparse.get(&address).cloned().unwrap_or(0),
+        }
+    }
+
+    pub(crate) fn set(&mut self, address: usize, value: i64) {
+        let len = self.dense().len();
+        if address < len {
+            self.dense.to_mut()[address] = value;
+        } else if address - len < MAX_DENSE_GROWTH {
+            let dense = self.dense.to_mut();
+            let pages = address / PAGE_SIZE + 1;
+            dense.resize(pages * PAGE_SIZE, 0);
+            dense[address] = value;
+            // Anything the dense region just swallowed has to move over.
+            let len = dense.len();
+            self.sparse.retain(|&a, &mut v| {
+                if a < len {
+                    dense[a] = v;
+                    false
+                } else {
+                    true
+                }
+            });
+        } else {
+            self.sparse.insert(address, value);
+        }
+    }
+
+    pub(crate) fn dense(&self) -> &[i64] {
+        match &self.dense {
+            Dense::Shared(image) => image,
+            Dense::Owned(dense) => dense,
+        }
+    }
+
+    // Whether the dense part is still a program image shared with other
+    // machines, and so mustn't be written in place.
+    #[cfg(feature = "jit")]
+    pub(crate) fn is_shared(&self) -> bool {
+        matches!(self.dense, Dense::Shared(_))
+    }
+
+    // The dense part for writing in place, without growing it.
+    #[cfg(feature = "jit")]
+    pub(crate) fn dense_mut(&mut self) -> &mut [i64] {
+        self.dense.to_mut()
+    }
+
+    pub(crate) fn sparse(&self) -> BTreeMap<usize, i64> {
+        self.sparse.iter().map(|(&a, &v)| (a, v)).collect()
+    }
+
+    pub(crate) fn into_dense(self) -> Vec<i64> {
+        match self.dense {
+            Dense::Shared(image) => image.to_vec(),
+            Dense::Owned(dense) => dense,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_by_pages() {
+        let mut memory = Memory::new(vec![1, 2, 3]);
+        memory.set(3, 4);
+        assert_eq!(memory.dense().len(), PAGE_SIZE);
+        memory.set(5 * PAGE_SIZE + 7, 9);
+        assert_eq!(memory.dense().len(), 6 * PAGE_SIZE);
+        assert_eq!(memory.get(5 * PAGE_SIZE + 7), 9);
+        memory.set(6 * PAGE_SIZE + MAX_DENSE_GROWTH, 1);
+        assert_eq!(memory.dense().len(), 6 * PAGE_SIZE);
+        assert_eq!(memory.sparse().len(), 1);
+    }
+
+    #[test]
+    fn from_parts_folds_sparse_into_dense() {
+        let sparse: BTreeMap<usize, i64> = vec![(1, 7), (3, 8), (1 << 20, 9)].into_iter().collect();
+        let memory = Memory::from_parts(vec![0; 3], sparse);
+        assert_eq!(memory.dense(), [0, 7, 0]);
+        assert_eq!(memory.get(1), 7);
+        assert_eq!(memory.get(3), 8);
+        assert_eq!(memory.get(1 << 20), 9);
+        assert_eq!(
+            memory.sparse().into_iter().collect::<Vec<_>>(),
+            [(3, 8), (1 << 20, 9)]
+        );
+    }
+
+    fn allocated(memory: &Memory) -> usize {
+        match &memory.dense {
+            Dense::Owned(dense) => dense.capacity(),
+            Dense::Shared(_) => 0,
+        }
+    }
+
+    #[test]
+    fn one_far_write_allocates_about_that_far() {
+        // Growing to 2 * address would have allocated 100,000 words here.
+        let mut memory = Memory::new(vec![1101, 1, 1, 50_000, 99]);
+        memory.set(50_000, 2);
+        assert_eq!(memory.dense().len(), 49 * PAGE_SIZE);
+        assert!(allocated(&memory) < 50_000 + PAGE_SIZE);
+
+        // Too far to grow to at all.
+        let mut memory = Memory::new(vec![1101, 1, 1, 50_000, 99]);
+        memory.set(1 << 40, 2);
+        assert_eq!(allocated(&memory), 5);
+        assert_eq!(memory.get(1 << 40), 2);
+    }
+}
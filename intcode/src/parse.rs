@@ -0,0 +1,126 @@
+//! Reading programs in the puzzle input format: comma-separated signed
+//! integers, optionally surrounded by whitespace.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// Something other than a number where a number should be.
+    Expected { offset: usize },
+    /// A number too large to fit in an `i64`.
+    Overflow { offset: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Expected { offset } => write!(f, "expected a number at byte {}", offset),
+            ParseError::Overflow { offset } => {
+                write!(f, "number at byte {} doesn't fit in an i64", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a program, scanning the bytes directly rather than splitting and
+/// parsing each word as its own string.
+pub fn parse_program(text: &str) -> Result<Vec<i64>, ParseError> {
+    let bytes = text.as_bytes();
+    // Programs are mostly short numbers; this is a cheap overestimate.
+    let mut program = Vec::with_capacity(bytes.len() / 2);
+    let mut i = skip_whitespace(bytes, 0);
+    if i == bytes.len() {
+        return Ok(program);
+    }
+    loop {
+        let (value, next) = scan_integer(bytes, i)?;
+        program.push(value);
+        i = skip_whitespace(bytes, next);
+        match bytes.get(i) {
+            None => return Ok(program),
+            Some(b',') => i = skip_whitespace(bytes, i + 1),
+            Some(_) => return Err(ParseError::Expected { offset: i }),
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+// Accumulates negatively so that i64::MIN parses without overflowing.
+fn scan_integer(bytes: &[u8], start: usize) -> Result<(i64, usize), ParseError> {
+    let mut i = start;
+    let negative = bytes.get(i) == Some(&b'-');
+    if negative {
+        i += 1;
+    }
+    let digits = i;
+    let mut value: i64 = 0;
+    while let Some(&b) = bytes.get(i) {
+        if !b.is_ascii_digit() {
+            break;
+        }
+        value = value
+            .checked_mul(10)
+            .and_then(|v| v.checked_sub(i64::from(b - b'0')))
+            .ok_or(ParseError::Overflow { offset: start })?;
+        i += 1;
+    }
+    if i == digits {
+        return Err(ParseError::Expected { offset: start });
+    }
+    if negative {
+        Ok((value, i))
+    } else {
+        value
+            .checked_neg()
+            .map(|v| (v, i))
+            .ok_or(ParseError::Overflow { offset: start })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn programs() {
+        assert_eq!(parse_program("1,0,0,3,99\n"), Ok(vec![1, 0, 0, 3, 99]));
+        assert_eq!(
+            parse_program(" 109, -1 ,204\t,-1 "),
+            Ok(vec![109, -1, 204, -1])
+        );
+        assert_eq!(parse_program("\n"), Ok(vec![]));
+        assert_eq!(
+            parse_program("-9223372036854775808,9223372036854775807"),
+            Ok(vec![i64::MIN, i64::MAX])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse_program("1,,2"),
+            Err(ParseError::Expected { offset: 2 })
+        );
+        assert_eq!(
+            parse_program("1,2,"),
+            Err(ParseError::Expected { offset: 4 })
+        );
+        assert_eq!(
+            parse_program("1 2"),
+            Err(ParseError::Expected { offset: 2 })
+        );
+        assert_eq!(parse_program("-"), Err(ParseError::Expected { offset: 0 }));
+        assert_eq!(
+            parse_program("1,9223372036854775808"),
+            Err(ParseError::Overflow { offset: 2 })
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Runs random small programs on the VM and on a deliberately simple
+//! reference interpreter, and checks that the two agree on every output and
+//! on how the run ends. The VM keeps growing caches and fast paths; this
+//! interpreter is the spec they have to match.
+
+use intcode::{Intcode, MachinePool, Opcode, RunError, IO};
+use proptest::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+const BUDGET: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum End {
+    Halted,
+    OutOfBudget,
+    Failed(RunError),
+}
+
+// Answers inputs by cycling through a fixed list (zeros if it's empty).
+struct Cycle {
+    inputs: Vec<i64>,
+    next: usize,
+    outputs: Vec<i64>,
+}
+
+impl Cycle {
+    fn new(inputs: &[i64]) -> Cycle {
+        Cycle {
+            inputs: inputs.to_vec(),
+            next: 0,
+            outputs: Vec::new(),
+        }
+    }
+}
+
+impl IO for Cycle {
+    fn input(&mut self) -> i64 {
+        let v = self.inputs.get(self.next).cloned().unwrap_or(0);
+        self.next = (self.next + 1) % self.inputs.len().max(1);
+        v
+    }
+
+    fn output(&mut self, v: i64) {
+        self.outputs.push(v);
+    }
+}
+
+// The words a run leaves nonzero: everything two memories have to agree on,
+// however they're laid out.
+type Words = BTreeMap<usize, i64>;
+
+fn nonzero(words: impl IntoIterator<Item = (usize, i64)>) -> Words {
+    words.into_iter().filter(|&(_, v)| v != 0).collect()
+}
+
+fn run_machine(machine: &mut Intcode<Cycle>) -> (Vec<i64>, End, Words) {
+    let mut end = End::OutOfBudget;
+    for _ in 0..BUDGET {
+        match machine.step() {
+            Ok(step) if step.opcode == Opcode::Halt => {
+                end = End::Halted;
+                break;
+            }
+            Ok(_) => (),
+            Err(error) => {
+                end = End::Failed(error);
+                break;
+            }
+        }
+    }
+    let state = machine.snapshot();
+    let words = nonzero(state.ram.into_iter().enumerate().chain(state.sparse));
+    (std::mem::take(&mut machine.io_mut().outputs), end, words)
+}
+
+fn run_vm(program: &[i64], inputs: &[i64]) -> (Vec<i64>, End) {
+    let mut machine = Intcode::new(program.to_vec(), Cycle::new(inputs));
+    let (outputs, end, _) = run_machine(&mut machine);
+    (outputs, end)
+}
+
+// Where an operand lives once its mode has been applied.
+enum Operand {
+    Address(usize),
+    Value(i64),
+}
+
+struct Reference {
+    memory: HashMap<usize, i64>,
+    pc: usize,
+    relative_base: i64,
+}
+
+impl Reference {
+    fn get(&self, address: usize) -> i64 {
+        self.memory.get(&address).cloned().unwrap_or(0)
+    }
+
+    fn operand(&self, instruction: i64, n: usize) -> Result<Operand, RunError> {
+        let pc = self.pc;
+        let mode = instruction / [100, 1000, 10000][n] % 10;
+        let word = self.get(pc + 1 + n);
+        let address = match mode {
+            0 => word,
+            1 => return Ok(Operand::Value(word)),
+            2 => word
+                .checked_add(self.relative_base)
+                .ok_or(RunError::Overflow { pc })?,
+            _ => return Err(RunError::InvalidMode { pc, instruction }),
+        };
+        usize::try_from(address)
+            .map(Operand::Address)
+            .map_err(|_| RunError::NegativeAddress { pc, address })
+    }
+
+    fn read(&self, operand: &Operand) -> i64 {
+        match *operand {
+            Operand::Address(address) => self.get(address),
+            Operand::Value(value) => value,
+        }
+    }
+
+    fn write(&mut self, operand: &Operand, value: i64) -> Result<(), RunError> {
+        match *operand {
+            Operand::Address(address) => {
+                self.memory.insert(address, value);
+                Ok(())
+            }
+            Operand::Value(_) => Err(RunError::ImmediateWrite { pc: self.pc }),
+        }
+    }
+
+    fn jump(&mut self, target: i64) -> Result<(), RunError> {
+        self.pc = usize::try_from(target).map_err(|_| RunError::NegativeAddress {
+            pc: self.pc,
+            address: target,
+        })?;
+        Ok(())
+    }
+
+    // Executes one instruction, returning whether it was a halt.
+    fn step(&mut self, io: &mut Cycle) -> Result<bool, RunError> {
+        let pc = self.pc;
+        let instruction = self.get(pc);
+        let count = match instruction % 100 {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            99 => return Ok(true),
+            _ => return Err(RunError::InvalidOpcode { pc, instruction }),
+        };
+        let mut operands = Vec::new();
+        for n in 0..count {
+            operands.push(self.operand(instruction, n)?);
+        }
+        let a = || self.read(&operands[0]);
+        let b = || self.read(&operands[1]);
+        match instruction % 100 {
+            1 => {
+                let sum = a().checked_add(b()).ok_or(RunError::Overflow { pc })?;
+                self.write(&operands[2], sum)?;
+            }
+            2 => {
+                let product = a().checked_mul(b()).ok_or(RunError::Overflow { pc })?;
+                self.write(&operands[2], product)?;
+            }
+            3 => {
+                let value = io.input();
+                self.write(&operands[0], value)?;
+            }
+            4 => io.output(a()),
+            5 | 6 => {
+                if (a() != 0) == (instruction % 100 == 5) {
+                    return self.jump(b()).map(|_| false);
+                }
+            }
+            7 => {
+                let less = (a() < b()) as i64;
+                self.write(&operands[2], less)?;
+            }
+            8 => {
+                let equal = (a() == b()) as i64;
+                self.write(&operands[2], equal)?;
+            }
+            9 => {
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(a())
+                    .ok_or(RunError::Overflow { pc })?;
+            }
+            _ => unreachable!(),
+        }
+        self.pc += 1 + count;
+        Ok(false)
+    }
+}
+
+fn run_reference(program: &[i64], inputs: &[i64]) -> (Vec<i64>, End) {
+    let (outputs, end, _) = reference_with_memory(program, inputs);
+    (outputs, end)
+}
+
+fn reference_with_memory(program: &[i64], inputs: &[i64]) -> (Vec<i64>, End, Words) {
+    let mut machine = Reference {
+        memory: program.iter().cloned().enumerate().collect(),
+        pc: 0,
+        relative_base: 0,
+    };
+    let mut io = Cycle::new(inputs);
+    let mut end = End::OutOfBudget;
+    for _ in 0..BUDGET {
+        match machine.step(&mut io) {
+            Ok(true) => {
+                end = End::Halted;
+                break;
+            }
+            Ok(false) => (),
+            Err(error) => {
+                end = End::Failed(error);
+                break;
+            }
+        }
+    }
+    (io.outputs, end, nonzero(machine.memory))
+}
+
+// Operand words: mostly small addresses and values, with a sprinkling of
+// extremes that hit the overflow and negative-address paths.
+fn operand() -> impl Strategy<Value = i64> {
+    prop_oneof![
+        8 => -3..60i64,
+        1 => prop::sample::select(vec![i64::MIN, i64::MAX, -1 << 40, 1 << 40, 70_000]),
+    ]
+}
+
+// A whole instruction, opcode word and operands. Modes are mostly valid and
+// destinations mostly writable, so that programs run for a while.
+fn instruction() -> impl Strategy<Value = Vec<i64>> {
+    let mode = prop_oneof![10 => Just(0), 5 => Just(2), 4 => Just(1), 1 => Just(3)];
+    (
+        prop::sample::select(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 99]),
+        [mode.clone(), mode.clone(), mode],
+        [operand(), operand(), operand()],
+    )
+        .prop_map(|(opcode, modes, operands)| {
+            let count = match opcode {
+                1 | 2 | 7 | 8 => 3,
+                5 | 6 => 2,
+                3 | 4 | 9 => 1,
+                _ => 0,
+            };
+            let modes = modes.iter().rev().fold(0, |all, mode| all * 10 + mode);
+            let mut words = vec![opcode + 100 * modes];
+            words.extend_from_slice(&operands[..count]);
+            words
+        })
+}
+
+fn program() -> impl Strategy<Value = Vec<i64>> {
+    prop::collection::vec(instruction(), 1..16).prop_map(|instructions| instructions.concat())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn vm_matches_reference(
+        program in program(),
+        inputs in prop::collection::vec(-5..5i64, 0..4),
+    ) {
+        prop_assert_eq!(run_vm(&program, &inputs), run_reference(&program, &inputs));
+    }
+
+    #[test]
+    fn shared_images_match_reference(
+        program in program(),
+        inputs in prop::collection::vec(-5..5i64, 0..4),
+    ) {
+        let expected = reference_with_memory(&program, &inputs);
+        // Two machines on one image, each copying it only if it writes.
+        let image: Arc<[i64]> = Arc::from(&program[..]);
+        let mut first = Intcode::shared(Arc::clone(&image), Cycle::new(&inputs));
+        let mut second = Intcode::shared(Arc::clone(&image), Cycle::new(&inputs));
+        prop_assert_eq!(run_machine(&mut first), expected.clone());
+        prop_assert_eq!(run_machine(&mut second), expected.clone());
+        prop_assert_eq!(&image[..], &program[..]);
+
+        // Recycling hands their memory on, whether it was copied or not.
+        let mut pool = MachinePool::new(program.clone());
+        pool.recycle(first);
+        pool.recycle(second);
+        for _ in 0..2 {
+            let mut machine = pool.machine(Cycle::new(&inputs));
+            prop_assert_eq!(run_machine(&mut machine), expected.clone());
+        }
+    }
+}
+
+#[test]
+fn reference_runs_day9_quine() {
+    let quine = [
+        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+    ];
+    assert_eq!(run_reference(&quine, &[]), (quine.to_vec(), End::Halted));
+    assert_eq!(run_vm(&quine, &[]), (quine.to_vec(), End::Halted));
+}
@@ -0,0 +1,117 @@
+//! The programs in `samples/`: each does what its README entry says, and each
+//! README listing still assembles to the committed program.
+
+use intcode::asm::assemble;
+use intcode::parse::parse_program;
+use intcode::Intcode;
+
+const README: &str = include_str!("../samples/README.md");
+const QUINE: &str = include_str!("../samples/quine.intcode");
+const MULTIPLY: &str = include_str!("../samples/multiply.intcode");
+const PRIME: &str = include_str!("../samples/prime.intcode");
+const SIEVE: &str = include_str!("../samples/bench/sieve.intcode");
+const DEEP_RECURSION: &str = include_str!("../samples/bench/deep_recursion.intcode");
+
+fn parse(program: &str) -> Vec<i64> {
+    parse_program(program).expect("sample doesn't parse")
+}
+
+fn run(program: &str, inputs: &[i64]) -> Vec<i64> {
+    let mut io = (inputs.iter().cloned(), Vec::new());
+    Intcode::new(parse(program), &mut io)
+        .run()
+        .expect("sample crashed");
+    io.1
+}
+
+#[test]
+fn quine() {
+    assert_eq!(run(QUINE, &[]), parse(QUINE));
+}
+
+#[test]
+fn multiply() {
+    for &(a, b) in &[(0, 5), (6, 7), (-3, 4), (123_456, 654_321)] {
+        assert_eq!(run(MULTIPLY, &[a, b]), [a * b]);
+    }
+}
+
+#[test]
+fn prime() {
+    let primes: Vec<i64> = (0..100).filter(|&n| run(PRIME, &[n]) == [1]).collect();
+    assert_eq!(
+        primes,
+        [
+            2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83,
+            89, 97
+        ]
+    );
+}
+
+#[test]
+fn sieve() {
+    for &(n, sum) in &[
+        (0, 0),
+        (3, 2),
+        (10, 17),
+        (1000, 76127),
+        (100_000, 454_396_537),
+    ] {
+        assert_eq!(run(SIEVE, &[n]), [sum]);
+    }
+}
+
+#[test]
+fn deep_recursion() {
+    for &(m, n, a) in &[(0, 0, 1), (1, 2, 4), (2, 3, 9), (3, 3, 61), (3, 6, 509)] {
+        assert_eq!(run(DEEP_RECURSION, &[m, n]), [a]);
+    }
+}
+
+// Each indented block in the README, along with the `.intcode` file named by
+// the heading above it.
+fn listings() -> Vec<(&'static str, String)> {
+    let mut listings: Vec<(&str, String)> = Vec::new();
+    let mut file = None;
+    for line in README.lines() {
+        if line.starts_with('#') {
+            file = line
+                .trim_start_matches('#')
+                .trim()
+                .strip_suffix(".intcode")
+                .map(|_| line.trim_start_matches('#').trim());
+        } else if let (Some(name), Some(code)) = (file, line.strip_prefix("    ")) {
+            match listings.last_mut() {
+                Some((last, listing)) if *last == name => {
+                    listing.push_str(code);
+                    listing.push('\n');
+                }
+                _ => listings.push((name, format!("{}\n", code))),
+            }
+        }
+    }
+    listings
+}
+
+#[test]
+fn readme_listings_match_programs() {
+    let programs = [
+        ("multiply.intcode", MULTIPLY),
+        ("prime.intcode", PRIME),
+        ("bench/sieve.intcode", SIEVE),
+        ("bench/deep_recursion.intcode", DEEP_RECURSION),
+    ];
+    let listings = listings();
+    assert_eq!(
+        listings.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+        programs.iter().map(|(name, _)| *name).collect::<Vec<_>>()
+    );
+    for ((name, listing), (_, program)) in listings.iter().zip(&programs) {
+        assert_eq!(
+            assemble(listing).unwrap(),
+            parse(program),
+            "{} doesn't match its listing",
+            name
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Machine state checkpointed through serde, as with the `serde` feature.
+
+#![cfg(feature = "serde")]
+
+use intcode::io::QueueIo;
+use intcode::{CostModel, Intcode, IntcodeState, OverflowPolicy, StopReason};
+
+const FAR_WRITE: &str = include_str!("fixtures/far_write.json");
+
+#[test]
+fn state_matches_fixture() {
+    let mut machine = Intcode::new(vec![1101, 7, 0, 1_000_000, 109, 3, 99], QueueIo::new());
+    assert_eq!(machine.run(), Ok(StopReason::Halted));
+    let expected: IntcodeState = serde_json::from_str(FAR_WRITE).unwrap();
+    assert_eq!(machine.snapshot(), expected);
+}
+
+#[test]
+fn checkpoint_and_resume() {
+    // Outputs 1, 2, 3, ... until it reaches 5.
+    let program = vec![
+        1001, 14, 1, 14, 4, 14, 1007, 14, 5, 15, 1005, 15, 0, 99, 0, 0,
+    ];
+    let mut machine = Intcode::new(program.clone(), QueueIo::new());
+    assert_eq!(machine.run_until_event(), Ok(StopReason::Output(1)));
+    assert_eq!(machine.run_until_event(), Ok(StopReason::Output(2)));
+    let checkpoint = serde_json::to_string(&machine.snapshot()).unwrap();
+
+    let restored: IntcodeState = serde_json::from_str(&checkpoint).unwrap();
+    assert_eq!(restored, machine.snapshot());
+    let mut resumed = Intcode::new(Vec::new(), QueueIo::new());
+    resumed.restore(restored);
+    let mut outputs = Vec::new();
+    while let StopReason::Output(v) = resumed.run_until_event().unwrap() {
+        outputs.push(v);
+    }
+    assert_eq!(outputs, [3, 4, 5]);
+}
+
+#[test]
+fn settings_round_trip() {
+    let model = CostModel {
+        custom: 10,
+        ..CostModel::default()
+    };
+    let json = serde_json::to_string(&model).unwrap();
+    assert_eq!(serde_json::from_str::<CostModel>(&json).unwrap(), model);
+
+    for &policy in &[
+        OverflowPolicy::Error,
+        OverflowPolicy::Saturate,
+        OverflowPolicy::Wrap,
+    ] {
+        let json = serde_json::to_string(&policy).unwrap();
+        assert_eq!(
+            serde_json::from_str::<OverflowPolicy>(&json).unwrap(),
+            policy
+        );
+    }
+}
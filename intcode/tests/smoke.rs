@@ -0,0 +1,74 @@
+//! Uses the crate the way a dependent would, through the public API only, on
+//! the examples from the puzzle texts.
+
+use intcode::parse::parse_program;
+use intcode::{run_collect, run_program, run_to_first_output, Intcode, StopReason, IO};
+
+#[test]
+fn one_shot_helpers() {
+    let equals_eight = parse_program("3,9,8,9,10,9,4,9,99,-1,8\n").unwrap();
+    assert_eq!(run_to_first_output(&equals_eight, &[8]).unwrap(), 1);
+    assert_eq!(run_to_first_output(&equals_eight, &[7]).unwrap(), 0);
+
+    let big = parse_program("1102,34915192,34915192,7,4,7,99,0").unwrap();
+    assert_eq!(run_collect(&big, &[]).unwrap(), [1_219_070_632_396_864]);
+
+    let finished = run_program(
+        &parse_program("1,9,10,3,2,3,11,0,99,30,40,50").unwrap(),
+        &[],
+    )
+    .unwrap();
+    assert!(finished.outputs.is_empty());
+    assert_eq!(finished.ram[0], 3500);
+}
+
+// A caller-side IO that feeds fixed inputs and keeps what comes out.
+struct Script {
+    inputs: Vec<i64>,
+    outputs: Vec<i64>,
+}
+
+impl IO for Script {
+    fn input(&mut self) -> i64 {
+        self.inputs.remove(0)
+    }
+
+    fn output(&mut self, v: i64) {
+        self.outputs.push(v);
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        if self.inputs.is_empty() {
+            None
+        } else {
+            Some(self.inputs.remove(0))
+        }
+    }
+}
+
+#[test]
+fn custom_io() {
+    let quine = parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99").unwrap();
+    let mut machine = Intcode::new(
+        quine.clone(),
+        Script {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        },
+    );
+    assert_eq!(machine.run().unwrap(), StopReason::Halted);
+    assert_eq!(machine.into_io().outputs, quine);
+
+    let echo = parse_program("3,0,4,0,99").unwrap();
+    let mut machine = Intcode::new(
+        echo,
+        Script {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        },
+    );
+    assert_eq!(machine.run_until_event().unwrap(), StopReason::NeedsInput);
+    machine.io_mut().inputs.push(42);
+    assert_eq!(machine.run_until_event().unwrap(), StopReason::Output(42));
+    assert_eq!(machine.run_until_event().unwrap(), StopReason::Halted);
+}
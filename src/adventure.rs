@@ -0,0 +1,275 @@
+//! The text adventure spoken by day 25's droid (and anything else using the
+//! same format): rooms with doors and items, and the commands that move
+//! between them.
+
+use aoc_utils::geom::Direction;
+use std::fmt;
+use std::iter::Peekable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Door {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Door {
+    pub fn name(self) -> &'static str {
+        match self {
+            Door::North => "north",
+            Door::South => "south",
+            Door::East => "east",
+            Door::West => "west",
+        }
+    }
+
+    pub fn opposite(self) -> Door {
+        match self {
+            Door::North => Door::South,
+            Door::South => Door::North,
+            Door::East => Door::West,
+            Door::West => Door::East,
+        }
+    }
+
+    fn parse(name: &str) -> Option<Door> {
+        match name {
+            "north" => Some(Door::North),
+            "south" => Some(Door::South),
+            "east" => Some(Door::East),
+            "west" => Some(Door::West),
+            _ => None,
+        }
+    }
+}
+
+impl From<Door> for Direction {
+    fn from(door: Door) -> Self {
+        match door {
+            Door::North => Direction::North,
+            Door::South => Direction::South,
+            Door::East => Direction::East,
+            Door::West => Direction::West,
+        }
+    }
+}
+
+impl From<Direction> for Door {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::North => Door::North,
+            Direction::South => Door::South,
+            Direction::East => Door::East,
+            Direction::West => Door::West,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Item(pub String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Room {
+    pub name: String,
+    pub description: String,
+    pub doors: Vec<Door>,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Go(Door),
+    Take(Item),
+    Drop(Item),
+    Inventory,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Go(door) => write!(f, "{}", door.name()),
+            Command::Take(item) => write!(f, "take {}", item.0),
+            Command::Drop(item) => write!(f, "drop {}", item.0),
+            Command::Inventory => write!(f, "inv"),
+        }
+    }
+}
+
+/// Every room described in `text`, in order. One command can describe more
+/// than one room, e.g. when a security checkpoint throws the droid back out.
+pub fn parse_rooms(text: &str) -> Vec<Room> {
+    let mut rooms = Vec::new();
+    let mut lines = text.lines().map(str::trim).peekable();
+    while let Some(line) = lines.next() {
+        let name = match room_name(line) {
+            Some(name) => name,
+            None => continue,
+        };
+        let mut room = Room {
+            name: name.to_string(),
+            description: String::new(),
+            doors: Vec::new(),
+            items: Vec::new(),
+        };
+        while let Some(&line) = lines.peek() {
+            if room_name(line).is_some() || line == "Command?" {
+                break;
+            }
+            lines.next();
+            match line {
+                "Doors here lead:" => {
+                    room.doors = list(&mut lines)
+                        .into_iter()
+                        .filter_map(Door::parse)
+                        .collect()
+                }
+                "Items here:" => {
+                    room.items = list(&mut lines)
+                        .into_iter()
+                        .map(|s| Item(s.to_string()))
+                        .collect()
+                }
+                "" => (),
+                _ if room.description.is_empty() => room.description = line.to_string(),
+                // Anything else is a reaction to the droid (an alert, say),
+                // not part of the room.
+                _ => (),
+            }
+        }
+        rooms.push(room);
+    }
+    rooms
+}
+
+/// The items listed by an `inv` command.
+pub fn parse_inventory(text: &str) -> Vec<Item> {
+    let mut lines = text.lines().map(str::trim).peekable();
+    while let Some(line) = lines.next() {
+        if line == "Items in your inventory:" {
+            return list(&mut lines)
+                .into_iter()
+                .map(|s| Item(s.to_string()))
+                .collect();
+        }
+    }
+    Vec::new()
+}
+
+fn room_name(line: &str) -> Option<&str> {
+    if line.len() > 6 && line.starts_with("== ") && line.ends_with(" ==") {
+        Some(&line[3..line.len() - 3])
+    } else {
+        None
+    }
+}
+
+// The "- entry" lines immediately following a list heading.
+fn list<'a, I>(lines: &mut Peekable<I>) -> Vec<&'a str>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut entries = Vec::new();
+    while let Some(entry) = lines.peek().and_then(|line| line.strip_prefix("- ")) {
+        entries.push(entry);
+        lines.next();
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HULL_BREACH: &str = "
+
+
+== Hull Breach ==
+You got in through a hole in the floor here. To keep your ship from also freezing, the hole has been sealed.
+
+Doors here lead:
+- north
+- east
+- west
+
+Command?
+";
+
+    const CHECKPOINT: &str = "
+
+
+== Pressure-Sensitive Floor ==
+Analyzing...
+
+Doors here lead:
+- south
+
+A loud, robotic voice says \"Alert! Droids on this ship are heavier than the detected value!\" and you are ejected back to the checkpoint.
+
+
+
+== Security Checkpoint ==
+In the next room, a pressure-sensitive floor will verify your identity.
+
+Doors here lead:
+- north
+- west
+
+Items here:
+- mutex
+- space law space brochure
+
+Command?
+";
+
+    #[test]
+    fn single_room() {
+        assert_eq!(
+            parse_rooms(HULL_BREACH),
+            [Room {
+                name: "Hull Breach".to_string(),
+                description: "You got in through a hole in the floor here. To keep your ship from also freezing, the hole has been sealed.".to_string(),
+                doors: vec![Door::North, Door::East, Door::West],
+                items: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn ejected_from_checkpoint() {
+        let rooms = parse_rooms(CHECKPOINT);
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[0].name, "Pressure-Sensitive Floor");
+        assert_eq!(rooms[0].description, "Analyzing...");
+        assert_eq!(rooms[0].doors, [Door::South]);
+        assert_eq!(rooms[1].name, "Security Checkpoint");
+        assert_eq!(rooms[1].doors, [Door::North, Door::West]);
+        assert_eq!(
+            rooms[1].items,
+            [
+                Item("mutex".to_string()),
+                Item("space law space brochure".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn inventory_and_commands() {
+        let text = "\nItems in your inventory:\n- mug\n- easter egg\n\nCommand?\n";
+        assert_eq!(
+            parse_inventory(text),
+            [Item("mug".to_string()), Item("easter egg".to_string())]
+        );
+        assert_eq!(parse_inventory("\nYou aren't carrying any items.\n"), []);
+        assert_eq!(Command::Go(Door::West).to_string(), "west");
+        for &direction in &Direction::ALL {
+            let door = Door::from(direction);
+            assert_eq!(Door::parse(door.name()), Some(door));
+            assert_eq!(Direction::from(door.opposite()), direction.reverse());
+        }
+        assert_eq!(
+            Command::Take(Item("mug".to_string())).to_string(),
+            "take mug"
+        );
+    }
+}
@@ -1,7 +1,8 @@
-use itertools::{iproduct, Itertools};
+use aoc_utils::geom::PointIterExt;
+use aoc_utils::grid::Grid;
+use itertools::Itertools;
 use num::Integer;
-use std::convert::TryFrom;
-use std::ops::{Add, Sub};
+use std::ops::Sub;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 enum Cell {
@@ -48,23 +49,6 @@ impl Ray {
             Ray(x, y)
         }
     }
-
-    fn angle(&self) -> f64 {
-        let y = self.1 as f64;
-        let x = self.0 as f64;
-        // This is a strange formulation:
-        //  - x.atan2(y) rotates us so that the -pi/pi boundary point is "up"
-        //  - negating the whole thing gives us the clockwise rotation we need
-        -(x.atan2(y))
-    }
-}
-
-impl Add<Ray> for Position {
-    type Output = Self;
-
-    fn add(self, other: Ray) -> Self {
-        Self(self.0 + other.0, self.1 + other.1)
-    }
 }
 
 impl Sub for Position {
@@ -77,43 +61,21 @@ impl Sub for Position {
 
 #[derive(Debug)]
 struct Map {
-    rows: Vec<Vec<Cell>>,
-    width: i64,
-    height: i64,
+    grid: Grid<Cell>,
 }
 
 impl Map {
     fn new(input: &str) -> Map {
-        let rows: Vec<Vec<Cell>> = input
-            .split('\n')
-            .map(|row| row.chars().map(|c| Cell::parse(c)).collect())
-            .collect();
-        let height = rows.len() as i64;
-        let width = rows[0].len() as i64;
-
         Map {
-            rows,
-            width,
-            height,
+            grid: Grid::parse(input, Cell::parse),
         }
     }
 
-    fn cell(&self, position: Position) -> Option<Cell> {
-        let x = usize::try_from(position.0).ok()?;
-        let y = usize::try_from(position.1).ok()?;
-        self.rows.get(y)?.get(x).cloned()
-    }
-
-    fn cell_mut(&mut self, position: Position) -> Option<&mut Cell> {
-        let x = usize::try_from(position.0).ok()?;
-        let y = usize::try_from(position.1).ok()?;
-        self.rows.get_mut(y)?.get_mut(x)
-    }
-
     fn asteroids(&self) -> impl Iterator<Item = Position> + '_ {
-        iproduct!(0..self.width, 0..self.height)
-            .map(Position::new)
-            .filter(move |&p| self.cell(p) == Some(Cell::Asteroid))
+        self.grid
+            .enumerate_cells()
+            .filter(|&(_, &cell)| cell == Cell::Asteroid)
+            .map(|(p, _)| Position::new(p))
     }
 
     fn visible_from(&self, origin: Position) -> impl Iterator<Item = Ray> + '_ {
@@ -122,26 +84,10 @@ impl Map {
             .map(move |p| p - origin)
             .unique()
     }
-
-    fn fire_laser(&mut self, from: Position, direction: Ray) -> Option<Position> {
-        let mut position = from + direction;
-        loop {
-            match self.cell_mut(position) {
-                None => return None,
-                Some(Cell::Empty) => (),
-                Some(c) => {
-                    *c = Cell::Empty;
-                    return Some(position);
-                }
-            }
-
-            position = position + direction;
-        }
-    }
 }
 
 fn main() {
-    let mut map = Map::new(MAP);
+    let map = Map::new(MAP);
 
     let answer1 = map
         .asteroids()
@@ -150,29 +96,23 @@ fn main() {
         .unwrap();
     dbg!(answer1);
 
-    // This repeats some work already done above but... w/e.
+    // Sweeping the laser visits each direction once per rotation, so the
+    // n-th asteroid along a ray is vaporized in rotation n.
     let (_, base) = answer1;
-    let mut shot_number = 1;
-    loop {
-        let mut shots: Vec<Ray> = map.visible_from(base).collect();
-        shots.sort_by(|a, b| a.angle().partial_cmp(&b.angle()).unwrap());
-        if shots.is_empty() {
-            break;
-        }
-
-        for direction in shots {
-            let vaporized = map.fire_laser(base, direction).expect("blew something up");
-            if shot_number == 200 {
-                let answer2 = vaporized.0 * 100 + vaporized.1;
-                dbg!(answer2);
-                return;
-            }
-
-            shot_number += 1;
-        }
-    }
-
-    println!("fired {} shots", shot_number - 1);
+    let origin = (base.0, base.1);
+    let targets = map
+        .asteroids()
+        .filter(|&p| p != base)
+        .map(|p| (p.0, p.1))
+        .sorted_by_angle_around(origin)
+        .group_by_direction(origin);
+    let rotations = targets.iter().map(Vec::len).max().unwrap_or(0);
+    let vaporized = (0..rotations)
+        .flat_map(|rotation| targets.iter().filter_map(move |ray| ray.get(rotation)))
+        .nth(199)
+        .expect("at least 200 asteroids to vaporize");
+    let answer2 = vaporized.0 * 100 + vaporized.1;
+    dbg!(answer2);
 }
 
 const MAP: &str = ".###.###.###.#####.#
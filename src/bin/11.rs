@@ -1,26 +1,8 @@
-use advent2019::intcode::{Intcode, IO};
+use advent2019::protocol::{Color, Turn};
+use aoc_utils::geom::Direction;
+use intcode::{Intcode, RunError, IO};
 use std::collections::HashMap;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Color {
-    Black,
-    White,
-}
-
-impl From<Color> for i64 {
-    fn from(color: Color) -> Self {
-        match color {
-            Color::Black => 0,
-            Color::White => 1,
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Command {
-    TurnLeft,
-    TurnRight,
-}
+use std::convert::TryFrom;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum State {
@@ -30,7 +12,7 @@ enum State {
 
 struct Robot {
     position: (i64, i64),
-    direction: (i64, i64),
+    direction: Direction,
     state: State,
     panels: HashMap<(i64, i64), Color>,
 }
@@ -39,25 +21,11 @@ impl Robot {
     fn new() -> Robot {
         Robot {
             position: (0, 0),
-            direction: (0, -1),
+            direction: Direction::North,
             state: State::PaintColor,
             panels: HashMap::new(),
         }
     }
-
-    fn turn(&mut self, command: Command) {
-        self.direction = match (self.direction, command) {
-            ((0, -1), Command::TurnLeft) => (-1, 0),
-            ((0, 1), Command::TurnLeft) => (1, 0),
-            ((-1, 0), Command::TurnLeft) => (0, 1),
-            ((1, 0), Command::TurnLeft) => (0, -1),
-            ((0, -1), Command::TurnRight) => (1, 0),
-            ((0, 1), Command::TurnRight) => (-1, 0),
-            ((-1, 0), Command::TurnRight) => (0, -1),
-            ((1, 0), Command::TurnRight) => (0, 1),
-            _ => panic!("bad state"),
-        };
-    }
 }
 
 impl IO for Robot {
@@ -69,40 +37,30 @@ impl IO for Robot {
     fn output(&mut self, v: i64) {
         match self.state {
             State::PaintColor => {
-                let color = match v {
-                    0 => Color::Black,
-                    1 => Color::White,
-                    _ => unimplemented!(),
-                };
+                let color = Color::try_from(v).expect("robot painted a bad color");
                 self.panels.insert(self.position, color);
                 self.state = State::Command;
             }
             State::Command => {
-                let command = match v {
-                    0 => Command::TurnLeft,
-                    1 => Command::TurnRight,
-                    _ => unimplemented!(),
-                };
-                self.turn(command);
+                let turn = Turn::try_from(v).expect("robot issued a bad turn");
+                self.direction = turn.apply(self.direction);
                 self.state = State::PaintColor;
-
-                self.position.0 += self.direction.0;
-                self.position.1 += self.direction.1;
+                self.position = self.direction.step(self.position);
             }
         }
     }
 }
 
-fn main() {
+fn main() -> Result<(), RunError> {
     let mut robot = Robot::new();
-    Intcode::new(INPUT.to_vec(), &mut robot).run();
+    Intcode::new(INPUT.to_vec(), &mut robot).run()?;
 
     let answer1 = robot.panels.len();
     dbg!(answer1);
 
     let mut robot = Robot::new();
     robot.panels.insert((0, 0), Color::White);
-    Intcode::new(INPUT.to_vec(), &mut robot).run();
+    Intcode::new(INPUT.to_vec(), &mut robot).run()?;
 
     // Output netpbm on stdout.
     let x_min = robot.panels.keys().map(|p| p.0).min().unwrap();
@@ -119,8 +77,10 @@ fn main() {
                 Color::White => print!("0"),
             }
         }
-        print!("\n");
+        println!();
     }
+
+    Ok(())
 }
 
 const INPUT: &[i64] = &[
@@ -1,4 +1,8 @@
 use itertools::Itertools;
+use num::Integer;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::iter;
 use std::ops::AddAssign;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -8,9 +12,15 @@ struct Position {
     z: i32,
 }
 
+// Energies are summed in i64: the absolute value of an i32 coordinate alone
+// can overflow an i32, let alone three of them.
+fn abs_sum(x: i32, y: i32, z: i32) -> i64 {
+    i64::from(x).abs() + i64::from(y).abs() + i64::from(z).abs()
+}
+
 impl Position {
-    fn potential_energy(&self) -> i32 {
-        self.x.abs() + self.y.abs() + self.z.abs()
+    fn potential_energy(&self) -> i64 {
+        abs_sum(self.x, self.y, self.z)
     }
 }
 
@@ -22,24 +32,30 @@ struct Velocity {
 }
 
 impl Velocity {
-    fn kinetic_energy(&self) -> i32 {
-        self.x.abs() + self.y.abs() + self.z.abs()
+    fn kinetic_energy(&self) -> i64 {
+        abs_sum(self.x, self.y, self.z)
     }
 }
 
+// Panics on overflow in release builds too, rather than quietly wrapping into
+// a wrong answer.
+fn checked_step(a: i32, b: i32) -> i32 {
+    a.checked_add(b).expect("moon simulation overflowed i32")
+}
+
 impl AddAssign<Velocity> for Position {
     fn add_assign(&mut self, other: Velocity) {
-        self.x += other.x;
-        self.y += other.y;
-        self.z += other.z;
+        self.x = checked_step(self.x, other.x);
+        self.y = checked_step(self.y, other.y);
+        self.z = checked_step(self.z, other.z);
     }
 }
 
 impl AddAssign for Velocity {
     fn add_assign(&mut self, other: Self) {
-        self.x += other.x;
-        self.y += other.y;
-        self.z += other.z;
+        self.x = checked_step(self.x, other.x);
+        self.y = checked_step(self.y, other.y);
+        self.z = checked_step(self.z, other.z);
     }
 }
 
@@ -59,115 +75,602 @@ impl Moon {
 
     fn gravity(&self, toward: Position) -> Velocity {
         Velocity {
-            x: (toward.x - self.position.x).signum(),
-            y: (toward.y - self.position.y).signum(),
-            z: (toward.z - self.position.z).signum(),
+            x: toward.x.cmp(&self.position.x) as i32,
+            y: toward.y.cmp(&self.position.y) as i32,
+            z: toward.z.cmp(&self.position.z) as i32,
         }
     }
 
-    fn total_energy(&self) -> i32 {
-        self.position.potential_energy() * self.velocity.kinetic_energy()
+    fn total_energy(&self) -> i64 {
+        self.position
+            .potential_energy()
+            .checked_mul(self.velocity.kinetic_energy())
+            .expect("moon energy overflowed i64")
     }
 }
 
-fn step(system: &mut [Moon; 4]) {
-    for (a_idx, b_idx) in (0..4).tuple_combinations() {
-        let b_pos = system[b_idx].position;
-        let a = &mut system[a_idx];
-        a.velocity += a.gravity(b_pos);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MoonEnergy {
+    potential: i64,
+    kinetic: i64,
+    total: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AxisEnergy {
+    potential: i64,
+    kinetic: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EnergySample {
+    step: usize,
+    moons: [MoonEnergy; 4],
+    axes: [AxisEnergy; 3],
+}
+
+type AxisState = (i32, i32, i32, i32, i32, i32, i32, i32);
+
+// One axis of the system on its own. The axes never interact, so each one can
+// be simulated (and its period found) independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Axis {
+    position: [i32; 4],
+    velocity: [i32; 4],
+}
+
+impl Axis {
+    fn step(&mut self) {
+        for (a, b) in (0..4).tuple_combinations() {
+            let pull = self.position[b].cmp(&self.position[a]) as i32;
+            self.velocity[a] = checked_step(self.velocity[a], pull);
+            self.velocity[b] = checked_step(self.velocity[b], -pull);
+        }
 
-        let a_pos = system[a_idx].position;
-        let b = &mut system[b_idx];
-        b.velocity += b.gravity(a_pos);
+        for (position, &velocity) in self.position.iter_mut().zip(&self.velocity) {
+            *position = checked_step(*position, velocity);
+        }
     }
 
-    for moon in system.iter_mut() {
-        moon.position += moon.velocity;
+    // The step function is reversible, so the first state to repeat must be
+    // the initial one; there's no need to remember anything in between.
+    fn period(&self) -> i64 {
+        let mut axis = *self;
+        let mut steps = 0;
+        loop {
+            axis.step();
+            steps += 1;
+            if axis == *self {
+                return steps;
+            }
+        }
     }
 }
 
-fn x_axes(system: &[Moon; 4]) -> (i32, i32, i32, i32, i32, i32, i32, i32) {
-    (
-        system[0].position.x,
-        system[0].velocity.x,
-        system[1].position.x,
-        system[1].velocity.x,
-        system[2].position.x,
-        system[2].velocity.x,
-        system[3].position.x,
-        system[3].velocity.x,
-    )
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct System {
+    moons: [Moon; 4],
 }
 
-fn y_axes(system: &[Moon; 4]) -> (i32, i32, i32, i32, i32, i32, i32, i32) {
-    (
-        system[0].position.y,
-        system[0].velocity.y,
-        system[1].position.y,
-        system[1].velocity.y,
-        system[2].position.y,
-        system[2].velocity.y,
-        system[3].position.y,
-        system[3].velocity.y,
-    )
-}
+impl System {
+    fn new(moons: [Moon; 4]) -> System {
+        System { moons }
+    }
+
+    fn step(&mut self) {
+        for (a_idx, b_idx) in (0..4).tuple_combinations() {
+            let b_pos = self.moons[b_idx].position;
+            let a = &mut self.moons[a_idx];
+            a.velocity += a.gravity(b_pos);
+
+            let a_pos = self.moons[a_idx].position;
+            let b = &mut self.moons[b_idx];
+            b.velocity += b.gravity(a_pos);
+        }
+
+        for moon in self.moons.iter_mut() {
+            moon.position += moon.velocity;
+        }
+    }
+
+    /// This state followed by every state after it, one step apart, so that
+    /// `iter_states().nth(n)` is the system after `n` steps.
+    fn iter_states(&self) -> impl Iterator<Item = System> {
+        iter::successors(Some(*self), |system| {
+            let mut next = *system;
+            next.step();
+            Some(next)
+        })
+    }
+
+    fn total_energy(&self) -> i64 {
+        self.moons
+            .iter()
+            .try_fold(0i64, |sum, m| sum.checked_add(m.total_energy()))
+            .expect("system energy overflowed i64")
+    }
+
+    fn energy(&self, step: usize) -> EnergySample {
+        let moon = |m: &Moon| MoonEnergy {
+            potential: m.position.potential_energy(),
+            kinetic: m.velocity.kinetic_energy(),
+            total: m.total_energy(),
+        };
+        let axis = |p: fn(&Moon) -> (i32, i32)| AxisEnergy {
+            potential: self.moons.iter().map(|m| i64::from(p(m).0).abs()).sum(),
+            kinetic: self.moons.iter().map(|m| i64::from(p(m).1).abs()).sum(),
+        };
+        EnergySample {
+            step,
+            moons: [
+                moon(&self.moons[0]),
+                moon(&self.moons[1]),
+                moon(&self.moons[2]),
+                moon(&self.moons[3]),
+            ],
+            axes: [
+                axis(|m| (m.position.x, m.velocity.x)),
+                axis(|m| (m.position.y, m.velocity.y)),
+                axis(|m| (m.position.z, m.velocity.z)),
+            ],
+        }
+    }
+
+    /// Samples the energy every `every` steps, from the current state through
+    /// `steps` steps from now. Panics if `every` is zero.
+    fn energy_history(&self, steps: usize, every: usize) -> Vec<EnergySample> {
+        assert!(every > 0, "can't sample energy every 0 steps");
+        self.iter_states()
+            .take(steps + 1)
+            .enumerate()
+            .filter(|&(step, _)| step % every == 0)
+            .map(|(step, system)| system.energy(step))
+            .collect()
+    }
+
+    fn axes(&self) -> [Axis; 3] {
+        let axis = |p: fn(&Moon) -> (i32, i32)| Axis {
+            position: [
+                p(&self.moons[0]).0,
+                p(&self.moons[1]).0,
+                p(&self.moons[2]).0,
+                p(&self.moons[3]).0,
+            ],
+            velocity: [
+                p(&self.moons[0]).1,
+                p(&self.moons[1]).1,
+                p(&self.moons[2]).1,
+                p(&self.moons[3]).1,
+            ],
+        };
+        [
+            axis(|m| (m.position.x, m.velocity.x)),
+            axis(|m| (m.position.y, m.velocity.y)),
+            axis(|m| (m.position.z, m.velocity.z)),
+        ]
+    }
+
+    fn x_axes(&self) -> AxisState {
+        let m = &self.moons;
+        (
+            m[0].position.x,
+            m[0].velocity.x,
+            m[1].position.x,
+            m[1].velocity.x,
+            m[2].position.x,
+            m[2].velocity.x,
+            m[3].position.x,
+            m[3].velocity.x,
+        )
+    }
 
-fn z_axes(system: &[Moon; 4]) -> (i32, i32, i32, i32, i32, i32, i32, i32) {
-    (
-        system[0].position.z,
-        system[0].velocity.z,
-        system[1].position.z,
-        system[1].velocity.z,
-        system[2].position.z,
-        system[2].velocity.z,
-        system[3].position.z,
-        system[3].velocity.z,
-    )
+    fn y_axes(&self) -> AxisState {
+        let m = &self.moons;
+        (
+            m[0].position.y,
+            m[0].velocity.y,
+            m[1].position.y,
+            m[1].velocity.y,
+            m[2].position.y,
+            m[2].velocity.y,
+            m[3].position.y,
+            m[3].velocity.y,
+        )
+    }
+
+    fn z_axes(&self) -> AxisState {
+        let m = &self.moons;
+        (
+            m[0].position.z,
+            m[0].velocity.z,
+            m[1].position.z,
+            m[1].velocity.z,
+            m[2].position.z,
+            m[2].velocity.z,
+            m[3].position.z,
+            m[3].velocity.z,
+        )
+    }
 }
 
-fn main() {
-    let input: [Moon; 4] = [
-        Moon::new(14, 2, 8),
-        Moon::new(7, 4, 10),
-        Moon::new(1, 17, 16),
-        Moon::new(-4, -1, 1),
-    ];
+fn write_energy_csv<W: Write>(samples: &[EnergySample], mut out: W) -> io::Result<()> {
+    write!(out, "step")?;
+    for moon in 0..4 {
+        write!(
+            out,
+            ",moon{0}_potential,moon{0}_kinetic,moon{0}_total",
+            moon
+        )?;
+    }
+    for axis in &["x", "y", "z"] {
+        write!(out, ",{0}_potential,{0}_kinetic", axis)?;
+    }
+    writeln!(out)?;
 
-    let mut system = input;
-    for _ in 0..1000 {
-        step(&mut system);
+    for sample in samples {
+        write!(out, "{}", sample.step)?;
+        for moon in &sample.moons {
+            write!(out, ",{},{},{}", moon.potential, moon.kinetic, moon.total)?;
+        }
+        for axis in &sample.axes {
+            write!(out, ",{},{}", axis.potential, axis.kinetic)?;
+        }
+        writeln!(out)?;
     }
 
-    let answer1: i32 = system.iter().map(|m| m.total_energy()).sum();
-    dbg!(answer1);
+    Ok(())
+}
 
-    let mut system = input;
+// Steps the whole system until each axis has come back around to its initial
+// state.
+fn periods_by_tuples(input: &System) -> [i64; 3] {
+    let mut system = *input;
     let mut cycle = 0;
+    let mut periods = [None; 3];
 
-    let mut x_repeated = false;
-    let mut y_repeated = false;
-    let mut z_repeated = false;
-
-    while !x_repeated || !y_repeated || !z_repeated {
+    while periods.iter().any(Option::is_none) {
         cycle += 1;
-        step(&mut system);
+        system.step();
 
-        if !x_repeated && x_axes(&system) == x_axes(&input) {
+        if periods[0].is_none() && system.x_axes() == input.x_axes() {
             println!("x repeated after {} steps", cycle);
-            x_repeated = true;
+            periods[0] = Some(cycle);
         }
 
-        if !y_repeated && y_axes(&system) == y_axes(&input) {
+        if periods[1].is_none() && system.y_axes() == input.y_axes() {
             println!("y repeated after {} steps", cycle);
-            y_repeated = true;
+            periods[1] = Some(cycle);
         }
 
-        if !z_repeated && z_axes(&system) == z_axes(&input) {
+        if periods[2].is_none() && system.z_axes() == input.z_axes() {
             println!("z repeated after {} steps", cycle);
-            z_repeated = true;
+            periods[2] = Some(cycle);
         }
     }
 
-    let answer2: i64 = 420788524631496; // by hand, via lcm(108344, 231614, 268296)
+    [
+        periods[0].unwrap(),
+        periods[1].unwrap(),
+        periods[2].unwrap(),
+    ]
+}
+
+// Simulates each axis separately, comparing its raw position/velocity arrays
+// against the initial ones.
+fn periods_by_axes(input: &System) -> [i64; 3] {
+    let axes = input.axes();
+    [axes[0].period(), axes[1].period(), axes[2].period()]
+}
+
+fn main() -> io::Result<()> {
+    let input = System::new([
+        Moon::new(14, 2, 8),
+        Moon::new(7, 4, 10),
+        Moon::new(1, 17, 16),
+        Moon::new(-4, -1, 1),
+    ]);
+
+    // `--energy-csv <path> [--every <n>]` dumps the energy over the part 1
+    // run for plotting, and `--algo <tuples|axes|check>` picks how part 2
+    // finds the per-axis periods.
+    let args: Vec<String> = std::env::args().collect();
+    let flag = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+    };
+    if let Some(path) = flag("--energy-csv") {
+        let every = flag("--every").map_or(1, |n| match n.parse() {
+            Ok(every) if every > 0 => every,
+            _ => panic!("--every takes a positive step count, not {}", n),
+        });
+        let history = input.energy_history(1000, every);
+        write_energy_csv(&history, BufWriter::new(File::create(path)?))?;
+    }
+
+    let answer1 = input.iter_states().nth(1000).unwrap().total_energy();
+    dbg!(answer1);
+
+    let periods = match flag("--algo").map(String::as_str) {
+        None | Some("tuples") => periods_by_tuples(&input),
+        Some("axes") => periods_by_axes(&input),
+        Some("check") => {
+            let periods = periods_by_tuples(&input);
+            assert_eq!(periods, periods_by_axes(&input));
+            periods
+        }
+        Some(algo) => panic!("unknown --algo {} (expected tuples, axes or check)", algo),
+    };
+    let answer2 = periods.iter().fold(1, |acc: i64, p| acc.lcm(p));
     dbg!(answer2);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The first worked example, as published.
+    const EXAMPLE_STEPS: &str = "
+After 0 steps:
+pos=<x=-1, y=  0, z= 2>, vel=<x= 0, y= 0, z= 0>
+pos=<x= 2, y=-10, z=-7>, vel=<x= 0, y= 0, z= 0>
+pos=<x= 4, y= -8, z= 8>, vel=<x= 0, y= 0, z= 0>
+pos=<x= 3, y=  5, z=-1>, vel=<x= 0, y= 0, z= 0>
+
+
+After 1 steps:
+pos=<x= 2, y= -1, z= 1>, vel=<x= 3, y=-1, z=-1>
+pos=<x= 3, y= -7, z=-4>, vel=<x= 1, y= 3, z= 3>
+pos=<x= 1, y= -7, z= 5>, vel=<x=-3, y= 1, z=-3>
+pos=<x= 2, y=  2, z= 0>, vel=<x=-1, y=-3, z= 1>
+
+After 2 steps:
+pos=<x= 5, y= -3, z=-1>, vel=<x= 3, y=-2, z=-2>
+pos=<x= 1, y= -2, z= 2>, vel=<x=-2, y= 5, z= 6>
+pos=<x= 1, y= -4, z=-1>, vel=<x= 0, y= 3, z=-6>
+pos=<x= 1, y= -4, z= 2>, vel=<x=-1, y=-6, z= 2>
+
+After 3 steps:
+pos=<x= 5, y= -6, z=-1>, vel=<x= 0, y=-3, z= 0>
+pos=<x= 0, y=  0, z= 6>, vel=<x=-1, y= 2, z= 4>
+pos=<x= 2, y=  1, z=-5>, vel=<x= 1, y= 5, z=-4>
+pos=<x= 1, y= -8, z= 2>, vel=<x= 0, y=-4, z= 0>
+
+After 4 steps:
+pos=<x= 2, y= -8, z= 0>, vel=<x=-3, y=-2, z= 1>
+pos=<x= 2, y=  1, z= 7>, vel=<x= 2, y= 1, z= 1>
+pos=<x= 2, y=  3, z=-6>, vel=<x= 0, y= 2, z=-1>
+pos=<x= 2, y= -9, z= 1>, vel=<x= 1, y=-1, z=-1>
+
+After 5 steps:
+pos=<x=-1, y= -9, z= 2>, vel=<x=-3, y=-1, z= 2>
+pos=<x= 4, y=  1, z= 5>, vel=<x= 2, y= 0, z=-2>
+pos=<x= 2, y=  2, z=-4>, vel=<x= 0, y=-1, z= 2>
+pos=<x= 3, y= -7, z=-1>, vel=<x= 1, y= 2, z=-2>
+
+After 6 steps:
+pos=<x=-1, y= -7, z= 3>, vel=<x= 0, y= 2, z= 1>
+pos=<x= 3, y=  0, z= 0>, vel=<x=-1, y=-1, z=-5>
+pos=<x= 3, y= -2, z= 1>, vel=<x= 1, y=-4, z= 5>
+pos=<x= 3, y= -4, z=-2>, vel=<x= 0, y= 3, z=-1>
+
+After 7 steps:
+pos=<x= 2, y= -2, z= 1>, vel=<x= 3, y= 5, z=-2>
+pos=<x= 1, y= -4, z=-4>, vel=<x=-2, y=-4, z=-4>
+pos=<x= 3, y= -7, z= 5>, vel=<x= 0, y=-5, z= 4>
+pos=<x= 2, y=  0, z= 0>, vel=<x=-1, y= 4, z= 2>
+
+After 8 steps:
+pos=<x= 5, y=  2, z=-2>, vel=<x= 3, y= 4, z=-3>
+pos=<x= 2, y= -7, z=-5>, vel=<x= 1, y=-3, z=-1>
+pos=<x= 0, y= -9, z= 6>, vel=<x=-3, y=-2, z= 1>
+pos=<x= 1, y=  1, z= 3>, vel=<x=-1, y= 1, z= 3>
+
+After 9 steps:
+pos=<x= 5, y=  3, z=-4>, vel=<x= 0, y= 1, z=-2>
+pos=<x= 2, y= -9, z=-3>, vel=<x= 0, y=-2, z= 2>
+pos=<x= 0, y= -8, z= 4>, vel=<x= 0, y= 1, z=-2>
+pos=<x= 1, y=  1, z= 5>, vel=<x= 0, y= 0, z= 2>
+
+After 10 steps:
+pos=<x= 2, y=  1, z=-3>, vel=<x=-3, y=-2, z= 1>
+pos=<x= 1, y= -8, z= 0>, vel=<x=-1, y= 1, z= 3>
+pos=<x= 3, y= -6, z= 1>, vel=<x= 3, y= 2, z=-3>
+pos=<x= 2, y=  0, z= 4>, vel=<x= 1, y=-1, z=-1>
+";
+
+    fn example1() -> System {
+        System::new([
+            Moon::new(-1, 0, 2),
+            Moon::new(2, -10, -7),
+            Moon::new(4, -8, 8),
+            Moon::new(3, 5, -1),
+        ])
+    }
+
+    fn example2() -> System {
+        System::new([
+            Moon::new(-8, -10, 0),
+            Moon::new(5, 5, 10),
+            Moon::new(2, -7, 3),
+            Moon::new(9, -8, -3),
+        ])
+    }
+
+    // Every "pos=<...>, vel=<...>" line, four moons to a system.
+    fn parse_systems(text: &str) -> Vec<System> {
+        let moons: Vec<Moon> = text
+            .lines()
+            .filter(|line| line.starts_with("pos="))
+            .map(|line| {
+                let n: Vec<i32> = line
+                    .split(|c: char| c != '-' && !c.is_ascii_digit())
+                    .filter(|word| !word.is_empty())
+                    .map(|word| word.parse().unwrap())
+                    .collect();
+                Moon {
+                    position: Position {
+                        x: n[0],
+                        y: n[1],
+                        z: n[2],
+                    },
+                    velocity: Velocity {
+                        x: n[3],
+                        y: n[4],
+                        z: n[5],
+                    },
+                }
+            })
+            .collect();
+        moons
+            .chunks(4)
+            .map(|m| System::new([m[0], m[1], m[2], m[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn example1_steps() {
+        let expected = parse_systems(EXAMPLE_STEPS);
+        assert_eq!(expected.len(), 11);
+        for (step, (actual, expected)) in example1().iter_states().zip(expected).enumerate() {
+            assert_eq!(actual, expected, "after {} steps", step);
+        }
+        assert_eq!(
+            example1().iter_states().nth(10).unwrap().total_energy(),
+            179
+        );
+    }
+
+    #[test]
+    fn example2_after_100_steps() {
+        let expected = parse_systems(
+            "
+pos=<x=  8, y=-12, z= -9>, vel=<x= -7, y=  3, z=  0>
+pos=<x= 13, y= 16, z= -3>, vel=<x=  3, y=-11, z= -5>
+pos=<x=-29, y=-11, z= -1>, vel=<x= -3, y=  7, z=  4>
+pos=<x= 16, y=-13, z= 23>, vel=<x=  7, y=  1, z=  1>
+",
+        );
+        let system = example2().iter_states().nth(100).unwrap();
+        assert_eq!(system, expected[0]);
+        assert_eq!(system.total_energy(), 1940);
+    }
+
+    #[test]
+    fn energy_near_i32_limits() {
+        let moon = Moon {
+            position: Position {
+                x: i32::MAX,
+                y: 0,
+                z: 0,
+            },
+            velocity: Velocity {
+                x: i32::MIN,
+                y: 0,
+                z: 0,
+            },
+        };
+        assert_eq!(moon.total_energy(), (1 << 62) - (1 << 31));
+        // Pulled toward moons far away in both directions without
+        // overflowing the difference in position.
+        let mut system = System::new([
+            Moon::new(i32::MIN, 0, 0),
+            Moon::new(i32::MAX, 0, 0),
+            Moon::new(0, 0, 0),
+            Moon::new(0, 0, 0),
+        ]);
+        system.step();
+        assert_eq!(system.moons[0].velocity.x, 3);
+        assert_eq!(system.moons[1].velocity.x, -3);
+    }
+
+    #[test]
+    #[should_panic(expected = "overflowed")]
+    fn energy_overflow_panics() {
+        let mut system = example1();
+        system.moons[0].position = Position {
+            x: i32::MAX,
+            y: i32::MAX,
+            z: i32::MAX,
+        };
+        system.moons[0].velocity = Velocity {
+            x: i32::MIN,
+            y: i32::MIN,
+            z: i32::MIN,
+        };
+        system.total_energy();
+    }
+
+    #[test]
+    #[should_panic(expected = "overflowed")]
+    fn position_overflow_panics() {
+        let mut system = example1();
+        system.moons[0].velocity.x = i32::MAX;
+        system.step();
+    }
+
+    #[test]
+    fn energy_history_samples() {
+        let history = example1().energy_history(10, 5);
+        assert_eq!(
+            history.iter().map(|sample| sample.step).collect::<Vec<_>>(),
+            [0, 5, 10]
+        );
+        // Nothing moves before the first step.
+        assert!(history[0].moons.iter().all(|moon| moon.kinetic == 0));
+        assert_eq!(history[0].moons[1].potential, 19);
+
+        let last = &history[2];
+        let moons: i64 = last.moons.iter().map(|moon| moon.total).sum();
+        assert_eq!(moons, 179);
+        assert_eq!(last.moons[0].potential, 6);
+        assert_eq!(last.moons[0].kinetic, 6);
+        // Per-axis energies split the same positions and velocities the
+        // other way.
+        let [x, y, z] = last.axes;
+        assert_eq!(x.potential + y.potential + z.potential, 31);
+        assert_eq!((x.kinetic, y.kinetic, z.kinetic), (8, 6, 8));
+
+        assert_eq!(example1().energy_history(3, 5).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "every 0 steps")]
+    fn energy_history_every_zero_panics() {
+        example1().energy_history(10, 0);
+    }
+
+    #[test]
+    fn energy_csv() {
+        let mut csv = Vec::new();
+        write_energy_csv(&example1().energy_history(10, 10), &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("step,moon0_potential,moon0_kinetic,moon0_total,moon1_"));
+        assert!(lines[0].ends_with(",z_potential,z_kinetic"));
+        assert_eq!(lines[1], "0,3,0,0,19,0,0,20,0,0,9,0,0,10,0,23,0,18,0");
+        let last: Vec<i64> = lines[2].split(',').map(|n| n.parse().unwrap()).collect();
+        assert_eq!(last.len(), lines[0].split(',').count());
+        assert_eq!((last[0], last[1], last[2], last[3]), (10, 6, 6, 36));
+    }
+
+    #[test]
+    fn example_periods() {
+        let lcm = |periods: [i64; 3]| periods.iter().fold(1, |acc: i64, p| acc.lcm(p));
+        for &(system, steps) in &[(example1(), 2772), (example2(), 4_686_774_924)] {
+            assert_eq!(lcm(periods_by_axes(&system)), steps);
+        }
+    }
+
+    #[test]
+    fn period_algorithms_agree() {
+        assert_eq!(periods_by_axes(&example1()), [18, 28, 44]);
+        assert_eq!(periods_by_axes(&example2()), [2028, 5898, 4702]);
+        for system in &[example1(), example2()] {
+            assert_eq!(periods_by_tuples(system), periods_by_axes(system));
+        }
+    }
 }
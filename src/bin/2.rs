@@ -1,31 +1,10 @@
-fn run_program(mut ram: Vec<usize>) -> usize {
-    let mut pc = 0;
+use intcode::RunError;
 
-    loop {
-        match ram[pc] {
-            1 => {
-                let op1 = ram[ram[pc + 1]];
-                let op2 = ram[ram[pc + 2]];
-                let dest = ram[pc + 3];
-                ram[dest] = op1 + op2;
-                pc += 4;
-            }
-            2 => {
-                let op1 = ram[ram[pc + 1]];
-                let op2 = ram[ram[pc + 2]];
-                let dest = ram[pc + 3];
-                ram[dest] = op1 * op2;
-                pc += 4;
-            }
-            99 => break,
-            _ => unimplemented!(),
-        }
-    }
-
-    ram[0]
+fn run(ram: &[i64]) -> Result<i64, RunError> {
+    Ok(intcode::run_program(ram, &[])?.ram[0])
 }
 
-fn main() {
+fn main() -> Result<(), RunError> {
     let mut ram = vec![
         1, 0, 0, 3, 1, 1, 2, 3, 1, 3, 4, 3, 1, 5, 0, 3, 2, 10, 1, 19, 1, 5, 19, 23, 1, 23, 5, 27,
         1, 27, 13, 31, 1, 31, 5, 35, 1, 9, 35, 39, 2, 13, 39, 43, 1, 43, 10, 47, 1, 47, 13, 51, 2,
@@ -39,17 +18,19 @@ fn main() {
     ram[1] = 12;
     ram[2] = 2;
 
-    println!("{:?}", run_program(ram.clone()));
+    println!("{:?}", run(&ram)?);
 
     for noun in 0..100 {
         for verb in 0..100 {
             ram[1] = noun;
             ram[2] = verb;
 
-            if run_program(ram.clone()) == 19690720 {
+            if run(&ram)? == 19690720 {
                 println!("{}", 100 * noun + verb);
-                return;
+                return Ok(());
             }
         }
     }
+
+    Ok(())
 }
@@ -1,3 +1,7 @@
+use std::ops::RangeInclusive;
+
+const INPUT: RangeInclusive<u32> = 367479..=893698;
+
 fn meets_criteria(n: &u32) -> bool {
     let mut n = *n;
     let mut digits = [0; 6];
@@ -28,6 +32,91 @@ fn meets_criteria(n: &u32) -> bool {
     run_length == 2
 }
 
+fn brute_force(range: RangeInclusive<u32>) -> usize {
+    range.filter(meets_criteria).count()
+}
+
+// A non-decreasing password is fully described by how many times each digit
+// appears in it (stars and bars: C(15, 6) = 5005 of them), and its runs are
+// exactly those counts. So rather than walking the range, walk the counts and
+// keep the ones with a count of exactly 2 whose password lands in the range.
+fn combinatorial(range: RangeInclusive<u32>) -> usize {
+    fn count(
+        digit: u32,
+        remaining: u32,
+        password: u32,
+        has_pair: bool,
+        range: &RangeInclusive<u32>,
+    ) -> usize {
+        if remaining == 0 {
+            return (has_pair && range.contains(&password)) as usize;
+        }
+        if digit > 9 {
+            return 0;
+        }
+
+        (0..=remaining)
+            .map(|n| {
+                let password = (0..n).fold(password, |p, _| p * 10 + digit);
+                count(
+                    digit + 1,
+                    remaining - n,
+                    password,
+                    has_pair || n == 2,
+                    range,
+                )
+            })
+            .sum()
+    }
+
+    count(0, 6, 0, false, &range)
+}
+
 fn main() {
-    dbg!((367479..=893698).filter(meets_criteria).count());
+    let args: Vec<String> = std::env::args().collect();
+    let algo = args
+        .iter()
+        .position(|a| a == "--algo")
+        .and_then(|i| args.get(i + 1));
+    let answer = match algo.map(String::as_str) {
+        None | Some("brute-force") => brute_force(INPUT),
+        Some("combinatorial") => combinatorial(INPUT),
+        Some(algo) => panic!(
+            "unknown --algo {} (expected brute-force or combinatorial)",
+            algo
+        ),
+    };
+    dbg!(answer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc_utils::genlib::Rng;
+
+    #[test]
+    fn combinatorial_matches_brute_force() {
+        assert_eq!(combinatorial(INPUT), brute_force(INPUT));
+
+        let mut rng = Rng::new(4);
+        for _ in 0..100 {
+            let a = rng.below(1_000_000) as u32;
+            let b = rng.below(1_000_000) as u32;
+            let range = a.min(b)..=a.max(b);
+            assert_eq!(
+                combinatorial(range.clone()),
+                brute_force(range.clone()),
+                "{:?}",
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn combinatorial_edges() {
+        // Single passwords, valid and not.
+        assert_eq!(combinatorial(112233..=112233), 1);
+        assert_eq!(combinatorial(123444..=123444), 0);
+        assert_eq!(combinatorial(111122..=111122), 1);
+    }
 }
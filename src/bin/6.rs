@@ -1,5 +1,6 @@
+use aoc_utils::search::bfs;
 use reformation::Reformation;
-use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::collections::BTreeMap;
 
 const INPUT: &str = "PQK)Q5S
 8QF)BST
@@ -2617,7 +2618,7 @@ fn indirects(
     subject: &str,
     parents: &BTreeMap<String, String>,
 ) -> Vec<String> {
-    match cache.get(subject).map(|e| e.clone()) {
+    match cache.get(subject).cloned() {
         Some(v) => v,
         None => {
             if subject == "COM" {
@@ -2650,39 +2651,17 @@ fn main() {
     for (child, parent) in &orbits {
         children
             .entry(parent.to_string())
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(child.to_string())
     }
 
-    // Quick and dirty breadth-first search.
-    let start = orbits.get("YOU").unwrap().clone();
-    let target = orbits.get("SAN").unwrap().clone();
-
-    let mut discovered = BTreeSet::new();
-    let mut active = VecDeque::new();
-    active.push_back((start, 0));
-
-    while let Some((body, n)) = active.pop_front() {
-        if body == target {
-            dbg!(n);
-            break;
-        }
-
-        if let Some(children) = children.get(&body) {
-            for child in children {
-                if !discovered.contains(child) {
-                    discovered.insert(child.clone());
-                    active.push_back((child.clone(), n + 1));
-                }
-            }
-        }
-
-        if let Some(parent) = orbits.get(&body) {
-            let parent = parent.clone();
-            if !discovered.contains(&parent) {
-                discovered.insert(parent.clone());
-                active.push_back((parent, n + 1));
-            }
-        }
-    }
+    let start = orbits.get("YOU").unwrap().as_str();
+    let target = orbits.get("SAN").unwrap().as_str();
+    let distances = bfs(start, |&body, push| {
+        let children = children.get(body).into_iter().flatten();
+        let parent = orbits.get(body);
+        children.chain(parent).map(String::as_str).for_each(push)
+    });
+    let n = distances[target];
+    dbg!(n);
 }
@@ -62,7 +62,7 @@ fn main() {
         for column in row {
             print!("{}", column.render());
         }
-        print!("\n");
+        println!();
     }
 }
 
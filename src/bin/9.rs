@@ -1,28 +1,6 @@
-use advent2019::intcode::{Intcode, IO};
+use intcode::RunError;
 
-struct HumanIO;
-
-impl IO for HumanIO {
-    fn input(&mut self) -> i64 {
-        println!("Input required.");
-        loop {
-            let mut input = String::new();
-            std::io::stdin()
-                .read_line(&mut input)
-                .expect("input required");
-            if let Ok(n) = input.trim().parse::<i64>() {
-                return n;
-            }
-            println!("Invalid integer, try again.");
-        }
-    }
-
-    fn output(&mut self, v: i64) {
-        println!("{}", v);
-    }
-}
-
-fn main() {
+fn main() -> Result<(), RunError> {
     let ram = vec![
         1102, 34463338, 34463338, 63, 1007, 63, 34463338, 63, 1005, 63, 53, 1102, 1, 3, 1000, 109,
         988, 209, 12, 9, 1000, 209, 6, 209, 3, 203, 0, 1008, 1000, 1, 63, 1005, 63, 65, 1008, 1000,
@@ -75,5 +53,9 @@ fn main() {
         1, 968, 22101, 0, -2, -2, 109, -3, 2106, 0, 0,
     ];
 
-    Intcode::new(ram, &mut HumanIO).run();
+    // In test mode, BOOST lists any opcodes it thinks are broken before the
+    // keycode.
+    println!("{:?}", intcode::run_collect(&ram, &[1])?);
+    println!("{}", intcode::run_to_first_output(&ram, &[2])?);
+    Ok(())
 }
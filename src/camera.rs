@@ -0,0 +1,454 @@
+//! The ASCII output of day 17's vacuum robot and day 21's springdroid: lines
+//! of text, pictures (the scaffold seen by the camera, or the hull as the
+//! droid falls through it) ended by a blank line, and the one value too big
+//! to be ASCII that reports the answer.
+
+use aoc_utils::geom::{Direction, Point};
+use aoc_utils::grid::Grid;
+use std::fmt;
+
+/// What day 17's camera shows: which cells are scaffold, and the robot if
+/// it's in view. The cell under an upright robot counts as scaffold; one
+/// that's tumbling has fallen off it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CameraView {
+    pub scaffold: Grid<bool>,
+    pub robot: Option<Robot>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heading {
+    Facing(Direction),
+    /// `X`: the robot has fallen off the scaffold and is tumbling through
+    /// space.
+    Tumbling,
+}
+
+impl Heading {
+    /// Reads the camera's picture of the robot: `^`, `v`, `<`, `>` or `X`.
+    pub fn from_char(c: char) -> Option<Heading> {
+        match c {
+            '^' => Some(Heading::Facing(Direction::North)),
+            'v' => Some(Heading::Facing(Direction::South)),
+            '<' => Some(Heading::Facing(Direction::West)),
+            '>' => Some(Heading::Facing(Direction::East)),
+            'X' => Some(Heading::Tumbling),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Robot {
+    pub position: Point,
+    pub heading: Heading,
+}
+
+/// One step of day 21's failure replay: which cells of the hull are solid,
+/// and where the droid (`@`) is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HullView {
+    pub solid: Grid<bool>,
+    pub droid: Point,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Camera(CameraView),
+    Hull(HullView),
+    /// A line of anything else, e.g. a prompt, without its newline.
+    Text(String),
+    /// The value outside the ASCII range that the program ends with: the dust
+    /// collected on day 17, the hull damage on day 21.
+    Answer(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// A negative value, which is neither ASCII nor an answer.
+    NotAscii(i64),
+    /// A picture's rows weren't all the same width; `row` is the first one
+    /// that differs from those above it.
+    Ragged { row: usize },
+    /// Something other than a blank line came straight after a picture.
+    Unterminated,
+    /// The camera showed the robot in more than one place.
+    ManyRobots(Point, Point),
+    /// An upright robot with no scaffold next to it, so it can't be
+    /// standing on any.
+    OffScaffold(Robot),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::NotAscii(v) => write!(f, "{} is not ASCII", v),
+            FrameError::Ragged { row } => write!(f, "picture row {} is ragged", row),
+            FrameError::Unterminated => write!(f, "picture not ended by a blank line"),
+            FrameError::ManyRobots(a, b) => write!(f, "robots at both {:?} and {:?}", a, b),
+            FrameError::OffScaffold(robot) => write!(
+                f,
+                "robot at {:?} is upright but not on the scaffold",
+                robot.position
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+fn is_picture_row(line: &str) -> bool {
+    line.chars().all(|c| "#.^v<>X@".contains(c))
+}
+
+fn picture(rows: &[String]) -> Result<Frame, FrameError> {
+    let width = rows[0].len();
+    if let Some(row) = rows.iter().position(|row| row.len() != width) {
+        return Err(FrameError::Ragged { row });
+    }
+    let text = rows.join("\n");
+    let grid = Grid::parse(&text, |c| c);
+    if let Some((droid, _)) = grid.enumerate_cells().find(|&(_, &c)| c == '@') {
+        return Ok(Frame::Hull(HullView {
+            solid: Grid::parse(&text, |c| c == '#'),
+            droid,
+        }));
+    }
+    let mut robots = grid.enumerate_cells().filter_map(|(position, &c)| {
+        Heading::from_char(c).map(|heading| Robot { position, heading })
+    });
+    let robot = robots.next();
+    if let (Some(first), Some(second)) = (robot, robots.next()) {
+        return Err(FrameError::ManyRobots(first.position, second.position));
+    }
+    let scaffold = Grid::parse(&text, |c| c != '.' && c != 'X');
+    if let Some(
+        robot @ Robot {
+            position,
+            heading: Heading::Facing(_),
+        },
+    ) = robot
+    {
+        if !scaffold.neighbors4(position).any(|p| scaffold[p]) {
+            return Err(FrameError::OffScaffold(robot));
+        }
+    }
+    Ok(Frame::Camera(CameraView { scaffold, robot }))
+}
+
+/// Assembles frames one output value at a time, as they arrive from the IO.
+#[derive(Debug, Clone, Default)]
+pub struct FrameDecoder {
+    line: String,
+    rows: Vec<String>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> FrameDecoder {
+        FrameDecoder::default()
+    }
+
+    /// Returns a frame once `v` completes one: a line of text at its newline,
+    /// a picture at the blank line after it, and an answer straight away.
+    pub fn push(&mut self, v: i64) -> Option<Result<Frame, FrameError>> {
+        if v < 0 {
+            return Some(Err(FrameError::NotAscii(v)));
+        }
+        if v > 127 {
+            return Some(if self.rows.is_empty() {
+                Ok(Frame::Answer(v))
+            } else {
+                self.rows.clear();
+                Err(FrameError::Unterminated)
+            });
+        }
+        let c = v as u8 as char;
+        if c != '\n' {
+            self.line.push(c);
+            return None;
+        }
+        let line = std::mem::take(&mut self.line);
+        if line.is_empty() {
+            if self.rows.is_empty() {
+                return None;
+            }
+            let rows = std::mem::take(&mut self.rows);
+            return Some(picture(&rows));
+        }
+        if is_picture_row(&line) {
+            self.rows.push(line);
+            return None;
+        }
+        if !self.rows.is_empty() {
+            self.rows.clear();
+            return Some(Err(FrameError::Unterminated));
+        }
+        Some(Ok(Frame::Text(line)))
+    }
+
+    /// Decodes a whole captured output stream. A trailing partial line or
+    /// picture is ignored.
+    pub fn decode(outputs: &[i64]) -> Vec<Result<Frame, FrameError>> {
+        let mut decoder = FrameDecoder::new();
+        outputs.iter().filter_map(|&v| decoder.push(v)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ascii(text: &str) -> Vec<i64> {
+        text.bytes().map(i64::from).collect()
+    }
+
+    // The example view from day 17's puzzle text.
+    const SCAFFOLD: &str = "\
+..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^..
+
+";
+
+    #[test]
+    fn camera_view() {
+        let frames = FrameDecoder::decode(&ascii(SCAFFOLD));
+        let view = match &frames[..] {
+            [Ok(Frame::Camera(view))] => view,
+            _ => panic!("not a single camera view: {:?}", frames),
+        };
+        assert_eq!(
+            view.robot,
+            Some(Robot {
+                position: (10, 6),
+                heading: Heading::Facing(Direction::North)
+            })
+        );
+        assert!(view.scaffold[(10, 6)]);
+        let alignment: i64 = view
+            .scaffold
+            .points()
+            .filter(|&p| view.scaffold[p])
+            .filter(|&p| {
+                view.scaffold
+                    .neighbors4(p)
+                    .filter(|&q| view.scaffold[q])
+                    .count()
+                    == 4
+            })
+            .map(|(x, y)| x * y)
+            .sum();
+        assert_eq!(alignment, 76);
+    }
+
+    fn camera(text: &str) -> Result<CameraView, FrameError> {
+        match FrameDecoder::decode(&ascii(text)).pop() {
+            Some(Ok(Frame::Camera(view))) => Ok(view),
+            Some(Err(error)) => Err(error),
+            frame => panic!("not a camera view: {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn headings() {
+        for (picture, heading) in [
+            ("#>#\n\n", Heading::Facing(Direction::East)),
+            ("#<#\n\n", Heading::Facing(Direction::West)),
+            ("#v#\n\n", Heading::Facing(Direction::South)),
+            ("#^#\n\n", Heading::Facing(Direction::North)),
+        ]
+        .iter()
+        {
+            let view = camera(picture).unwrap();
+            assert_eq!(
+                view.robot,
+                Some(Robot {
+                    position: (1, 0),
+                    heading: *heading
+                })
+            );
+            assert!(view.scaffold[(1, 0)]);
+        }
+    }
+
+    #[test]
+    fn tumbling() {
+        let view = camera("..#..\n..#..\n..#.X\n\n").unwrap();
+        assert_eq!(
+            view.robot,
+            Some(Robot {
+                position: (4, 2),
+                heading: Heading::Tumbling
+            })
+        );
+        assert!(!view.scaffold[(4, 2)]);
+    }
+
+    #[test]
+    fn misplaced_robots() {
+        assert_eq!(
+            camera("###..\n....>\n\n"),
+            Err(FrameError::OffScaffold(Robot {
+                position: (4, 1),
+                heading: Heading::Facing(Direction::East)
+            }))
+        );
+        assert_eq!(
+            camera("#^#\n#v#\n\n"),
+            Err(FrameError::ManyRobots((1, 0), (1, 1)))
+        );
+        // Even a tumbling robot is only in one place.
+        assert_eq!(
+            camera("X.^#\n\n"),
+            Err(FrameError::ManyRobots((0, 0), (2, 0)))
+        );
+        assert_eq!(
+            FrameError::OffScaffold(Robot {
+                position: (4, 1),
+                heading: Heading::Facing(Direction::East)
+            })
+            .to_string(),
+            "robot at (4, 1) is upright but not on the scaffold"
+        );
+    }
+
+    #[test]
+    fn prompts_and_answer() {
+        let mut outputs = ascii(SCAFFOLD);
+        outputs.extend(ascii("Main:\nFunction A:\n\nContinuous video feed?\n"));
+        outputs.push(742_673);
+        let frames: Vec<_> = FrameDecoder::decode(&outputs)
+            .into_iter()
+            .skip(1)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            frames,
+            [
+                Frame::Text("Main:".to_string()),
+                Frame::Text("Function A:".to_string()),
+                Frame::Text("Continuous video feed?".to_string()),
+                Frame::Answer(742_673),
+            ]
+        );
+    }
+
+    // Day 21's puzzle text, with the droid jumping too early.
+    const FELL: &str = "\
+Input instructions:
+
+Walking...
+
+
+Didn't make it across:
+
+.................
+.................
+@................
+#####.###########
+
+.................
+.................
+.@...............
+#####.###########
+
+.................
+..@..............
+.................
+#####.###########
+
+...@.............
+.................
+.................
+#####.###########
+
+.................
+....@............
+.................
+#####.###########
+
+.................
+.................
+.....@...........
+#####.###########
+
+.................
+.................
+.................
+#####@###########
+
+";
+
+    #[test]
+    fn hull_replay() {
+        let frames: Vec<Frame> = FrameDecoder::decode(&ascii(FELL))
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            frames[..3],
+            [
+                Frame::Text("Input instructions:".to_string()),
+                Frame::Text("Walking...".to_string()),
+                Frame::Text("Didn't make it across:".to_string()),
+            ]
+        );
+        let hulls: Vec<&HullView> = frames[3..]
+            .iter()
+            .map(|frame| match frame {
+                Frame::Hull(hull) => hull,
+                _ => panic!("not a hull: {:?}", frame),
+            })
+            .collect();
+        let droid: Vec<Point> = hulls.iter().map(|hull| hull.droid).collect();
+        assert_eq!(
+            droid,
+            [(0, 2), (1, 2), (2, 1), (3, 0), (4, 1), (5, 2), (5, 3)]
+        );
+        // The hole the droid ends up in, and the cell it fell through.
+        assert!(!hulls[6].solid[(5, 3)]);
+        assert!(hulls[6].solid[(4, 3)]);
+        assert!(!hulls[5].solid[(5, 2)]);
+    }
+
+    #[test]
+    fn damage_report() {
+        let mut outputs = ascii("Input instructions:\n\nWalking...\n\n");
+        outputs.push(19_358_870);
+        assert_eq!(
+            FrameDecoder::decode(&outputs).pop(),
+            Some(Ok(Frame::Answer(19_358_870)))
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            FrameDecoder::decode(&ascii("#.#\n##\n\n")),
+            [Err(FrameError::Ragged { row: 1 })]
+        );
+        assert_eq!(
+            FrameDecoder::decode(&ascii("#.#\nMain:\n")),
+            [Err(FrameError::Unterminated)]
+        );
+        let mut outputs = ascii("#.#\n");
+        outputs.push(1000);
+        assert_eq!(
+            FrameDecoder::decode(&outputs),
+            [Err(FrameError::Unterminated)]
+        );
+        assert_eq!(FrameDecoder::decode(&[-1]), [Err(FrameError::NotAscii(-1))]);
+        // A stray value doesn't stop decoding.
+        assert_eq!(
+            FrameDecoder::decode(&[-1, 'o' as i64, 'k' as i64, 10]),
+            [
+                Err(FrameError::NotAscii(-1)),
+                Ok(Frame::Text("ok".to_string()))
+            ]
+        );
+    }
+}
@@ -0,0 +1,382 @@
+//! Day 20's donut maze: open tiles (`.`) and walls (`#`) in a square ring,
+//! with two-letter portal labels written outside the ring and inside its
+//! hole. `AA` and `ZZ` mark the entrance and exit rather than portals.
+
+use aoc_utils::geom::Point;
+use aoc_utils::grid::Grid;
+use std::collections::HashMap;
+
+/// Which edge of the ring a portal sits on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    /// Around the hole in the middle.
+    Inner,
+    /// Around the outside.
+    Outer,
+}
+
+impl Side {
+    /// How stepping through a portal on this side changes the recursion
+    /// level: inner portals lead one level deeper, outer ones back out.
+    pub fn level_change(self) -> i64 {
+        match self {
+            Side::Inner => 1,
+            Side::Outer => -1,
+        }
+    }
+}
+
+/// The smallest rectangle holding every tile of the ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+/// Which side a portal's tile is on: the ring's outer edge runs along
+/// `bounds`, so any tile on that edge is outer and every other is inner.
+pub fn classify_portal(pos: Point, bounds: Bounds) -> Side {
+    let (x, y) = pos;
+    if x == bounds.min.0 || x == bounds.max.0 || y == bounds.min.1 || y == bounds.max.1 {
+        Side::Outer
+    } else {
+        Side::Inner
+    }
+}
+
+/// One end of a portal (or the entrance or exit): its label and the open
+/// tile next to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Portal {
+    pub label: String,
+    pub tile: Point,
+    pub side: Side,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Donut {
+    /// The puzzle text as a grid, with short lines padded with spaces.
+    pub map: Grid<char>,
+    pub bounds: Bounds,
+    /// Every labelled tile, reading order of the label's first letter.
+    pub portals: Vec<Portal>,
+}
+
+impl Donut {
+    /// Parses the puzzle text. Labels read left to right or top to bottom,
+    /// and belong to the open tile at whichever end of them has one.
+    pub fn parse(text: &str) -> Donut {
+        let width = text.lines().map(str::len).max().unwrap_or(0);
+        let map = Grid::from_rows(text.lines().map(|line| {
+            let mut row: Vec<char> = line.chars().collect();
+            row.resize(width, ' ');
+            row
+        }));
+        let ring: Vec<Point> = map
+            .points()
+            .filter(|&p| map[p] == '.' || map[p] == '#')
+            .collect();
+        let bounds = Bounds {
+            min: (
+                ring.iter().map(|p| p.0).min().unwrap_or(0),
+                ring.iter().map(|p| p.1).min().unwrap_or(0),
+            ),
+            max: (
+                ring.iter().map(|p| p.0).max().unwrap_or(0),
+                ring.iter().map(|p| p.1).max().unwrap_or(0),
+            ),
+        };
+        let letter = |p: Point| map.get(p).filter(|c| c.is_ascii_uppercase()).cloned();
+        let open = |p: Point| map.get(p) == Some(&'.');
+        let mut portals = Vec::new();
+        for (x, y) in map.points() {
+            let first = match letter((x, y)) {
+                Some(c) => c,
+                None => continue,
+            };
+            for &(dx, dy) in &[(1, 0), (0, 1)] {
+                let second = match letter((x + dx, y + dy)) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let before = (x - dx, y - dy);
+                let after = (x + 2 * dx, y + 2 * dy);
+                if let Some(tile) = Some(before)
+                    .filter(|&p| open(p))
+                    .or(Some(after).filter(|&p| open(p)))
+                {
+                    portals.push(Portal {
+                        label: [first, second].iter().collect(),
+                        tile,
+                        side: classify_portal(tile, bounds),
+                    });
+                }
+            }
+        }
+        Donut {
+            map,
+            bounds,
+            portals,
+        }
+    }
+
+    fn find(&self, label: &str) -> Option<Point> {
+        self.portals
+            .iter()
+            .find(|portal| portal.label == label)
+            .map(|portal| portal.tile)
+    }
+
+    /// The `AA` tile.
+    pub fn entrance(&self) -> Option<Point> {
+        self.find("AA")
+    }
+
+    /// The `ZZ` tile.
+    pub fn exit(&self) -> Option<Point> {
+        self.find("ZZ")
+    }
+
+    /// Both ends of every portal, inner end first, in label order. Labels
+    /// without exactly one inner and one outer end (like `AA` and `ZZ`) are
+    /// left out.
+    pub fn portal_pairs(&self) -> Vec<(&Portal, &Portal)> {
+        let mut ends: HashMap<&str, Vec<&Portal>> = HashMap::new();
+        for portal in &self.portals {
+            ends.entry(&portal.label).or_default().push(portal);
+        }
+        let mut pairs: Vec<(&Portal, &Portal)> = ends
+            .into_values()
+            .filter_map(|ends| match ends[..] {
+                [a, b] if a.side == Side::Inner && b.side == Side::Outer => Some((a, b)),
+                [a, b] if a.side == Side::Outer && b.side == Side::Inner => Some((b, a)),
+                _ => None,
+            })
+            .collect();
+        pairs.sort_by(|a, b| a.0.label.cmp(&b.0.label));
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aoc_utils::search::bfs;
+
+    // The first example from the puzzle text.
+    const SMALL: &str = "
+         A
+         A
+  #######.#########
+  #######.........#
+  #######.#######.#
+  #######.#######.#
+  #######.#######.#
+  #####  B    ###.#
+BC...##  C    ###.#
+  ##.##       ###.#
+  ##...DE  F  ###.#
+  #####    G  ###.#
+  #########.#####.#
+DE..#######...###.#
+  #.#########.###.#
+FG..#########.....#
+  ###########.#####
+             Z
+             Z
+";
+
+    fn parse(text: &str) -> Donut {
+        Donut::parse(text.trim_start_matches('\n'))
+    }
+
+    // The shortest walk from AA to ZZ, treating portals as one step.
+    fn shortest(donut: &Donut) -> Option<usize> {
+        let mut warps = HashMap::new();
+        for (inner, outer) in donut.portal_pairs() {
+            warps.insert(inner.tile, outer.tile);
+            warps.insert(outer.tile, inner.tile);
+        }
+        bfs(donut.entrance()?, |&p, push| {
+            donut
+                .map
+                .neighbors4(p)
+                .filter(|&q| donut.map[q] == '.')
+                .for_each(&mut *push);
+            if let Some(&q) = warps.get(&p) {
+                push(q);
+            }
+        })
+        .get(&donut.exit()?)
+        .cloned()
+    }
+
+    #[test]
+    fn classifies_small_example() {
+        let donut = parse(SMALL);
+        assert_eq!(
+            donut.bounds,
+            Bounds {
+                min: (2, 2),
+                max: (18, 16)
+            }
+        );
+        assert_eq!(donut.entrance(), Some((9, 2)));
+        assert_eq!(donut.exit(), Some((13, 16)));
+        let pairs: Vec<(&str, Point, Point)> = donut
+            .portal_pairs()
+            .into_iter()
+            .map(|(inner, outer)| (&inner.label[..], inner.tile, outer.tile))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("BC", (9, 6), (2, 8)),
+                ("DE", (6, 10), (2, 13)),
+                ("FG", (11, 12), (2, 15)),
+            ]
+        );
+        assert_eq!(shortest(&donut), Some(23));
+    }
+
+    // The second, larger example from the puzzle text.
+    const LARGE: &str = "
+                   A
+                   A
+  #################.#############
+  #.#...#...................#.#.#
+  #.#.#.###.###.###.#########.#.#
+  #.#.#.......#...#.....#.#.#...#
+  #.#########.###.#####.#.#.###.#
+  #.............#.#.....#.......#
+  ###.###########.###.#.#.#.#.###
+  #.....#        A   C    #.#.#.#
+  #######        S   P    #####.#
+  #.#...#                 #......VT
+  #.#.#.#                 #.#####
+  #...#.#               YN....#.#
+  #.###.#                 #####.#
+DI....#.#                 #.....#
+  #####.#                 #.###.#
+ZZ......#               QG....#..AS
+  ###.###                 #######
+JO..#.#.#                 #.....#
+  #.#.#.#                 ###.#.#
+  #...#..DI             BU....#..LF
+  #####.#                 #.#####
+YN......#               VT..#....QG
+  #.###.#                 #.###.#
+  #.#...#                 #.....#
+  ###.###    J L     J    #.#.###
+  #.....#    O F     P    #.#...#
+  #.###.#####.#.#####.#####.###.#
+  #...#.#.#...#.....#.....#.#...#
+  #.#####.###.###.#.#.#########.#
+  #...#.#.....#...#.#.#.#.....#.#
+  #.###.#####.###.###.#.#.#######
+  #.#.........#...#.............#
+  #########.###.###.#############
+           B   J   C
+           U   P   P
+";
+
+    #[test]
+    fn classifies_large_example() {
+        let donut = parse(LARGE);
+        assert_eq!(
+            donut.bounds,
+            Bounds {
+                min: (2, 2),
+                max: (32, 34)
+            }
+        );
+        assert_eq!(donut.entrance(), Some((19, 2)));
+        assert_eq!(donut.exit(), Some((2, 17)));
+        let pairs: Vec<(&str, Point, Point)> = donut
+            .portal_pairs()
+            .into_iter()
+            .map(|(inner, outer)| (&inner.label[..], inner.tile, outer.tile))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("AS", (17, 8), (32, 17)),
+                ("BU", (26, 21), (11, 34)),
+                ("CP", (21, 8), (19, 34)),
+                ("DI", (8, 21), (2, 15)),
+                ("JO", (13, 28), (2, 19)),
+                ("JP", (21, 28), (15, 34)),
+                ("LF", (15, 28), (32, 21)),
+                ("QG", (26, 17), (32, 23)),
+                ("VT", (26, 23), (32, 11)),
+                ("YN", (26, 13), (2, 23)),
+            ]
+        );
+        assert_eq!(shortest(&donut), Some(58));
+    }
+
+    // Labels on every side of both edges, with each warp needed in turn.
+    const EVERY_SIDE: &str = "
+    A    B       F
+    A    C       G
+  ##.####.#######.###
+  ##.####...#####.###
+  ##.######.#####.###
+  ##.######.#####.###
+HI.#.######.#####.###
+  .#.##    D    #.###
+  .#.##    E    #.###
+  .#...BC     HI..###
+  .####      F  #####
+  .####      G  #####
+  .##########.#######
+  .##########.#######
+ZZ.##########..######
+  ############.######
+  ############.######
+              D
+              E
+";
+
+    #[test]
+    fn classifies_every_side() {
+        let donut = parse(EVERY_SIDE);
+        assert_eq!(
+            donut.bounds,
+            Bounds {
+                min: (2, 2),
+                max: (20, 16)
+            }
+        );
+        let pairs: Vec<(&str, Point, Point)> = donut
+            .portal_pairs()
+            .into_iter()
+            .map(|(inner, outer)| (&inner.label[..], inner.tile, outer.tile))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("BC", (6, 9), (9, 2)),
+                ("DE", (11, 6), (14, 16)),
+                ("FG", (13, 12), (17, 2)),
+                ("HI", (16, 9), (2, 6)),
+            ]
+        );
+        assert_eq!(shortest(&donut), Some(40));
+    }
+
+    #[test]
+    fn sides() {
+        let bounds = Bounds {
+            min: (2, 2),
+            max: (18, 16),
+        };
+        assert_eq!(classify_portal((2, 8), bounds), Side::Outer);
+        assert_eq!(classify_portal((18, 9), bounds), Side::Outer);
+        assert_eq!(classify_portal((9, 2), bounds), Side::Outer);
+        assert_eq!(classify_portal((13, 16), bounds), Side::Outer);
+        assert_eq!(classify_portal((9, 6), bounds), Side::Inner);
+        assert_eq!(Side::Inner.level_change(), 1);
+        assert_eq!(Side::Outer.level_change(), -1);
+    }
+}
@@ -0,0 +1,178 @@
+//! Day 15's repair droid: an IO wrapper that watches the movement commands
+//! going in and the status replies coming out, and keeps a map of everything
+//! the droid has found so far, whatever is doing the steering.
+
+use crate::protocol::{MovementCommand, StatusReply};
+use aoc_utils::geom::{Direction, Point};
+use intcode::IO;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// Passes everything through to `inner`, recording the droid's position and
+/// the reply for each square it tried to move into. Walls are recorded at the
+/// square the droid bumped into; the droid itself starts at the origin.
+///
+/// Inputs that aren't movement commands and outputs that aren't status
+/// replies (or that arrive without a command to answer) are passed through
+/// and otherwise ignored.
+#[derive(Debug, Clone)]
+pub struct MapRecorder<T> {
+    inner: T,
+    position: Point,
+    pending: Option<Direction>,
+    map: HashMap<Point, StatusReply>,
+}
+
+impl<T: IO> MapRecorder<T> {
+    pub fn new(inner: T) -> MapRecorder<T> {
+        let position = (0, 0);
+        let mut map = HashMap::new();
+        map.insert(position, StatusReply::Moved);
+        MapRecorder {
+            inner,
+            position,
+            pending: None,
+            map,
+        }
+    }
+
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    pub fn map(&self) -> &HashMap<Point, StatusReply> {
+        &self.map
+    }
+
+    /// Where the oxygen system is, once the droid has found it.
+    pub fn oxygen(&self) -> Option<Point> {
+        self.map
+            .iter()
+            .find(|&(_, &reply)| reply == StatusReply::FoundOxygen)
+            .map(|(&p, _)| p)
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn command(&mut self, v: i64) {
+        self.pending = MovementCommand::try_from(v).ok().map(Direction::from);
+    }
+}
+
+impl<T: IO> IO for MapRecorder<T> {
+    fn input(&mut self) -> i64 {
+        let v = self.inner.input();
+        self.command(v);
+        v
+    }
+
+    fn output(&mut self, v: i64) {
+        if let (Some(direction), Ok(reply)) = (self.pending.take(), StatusReply::try_from(v)) {
+            let target = direction.step(self.position);
+            self.map.insert(target, reply);
+            if reply != StatusReply::HitWall {
+                self.position = target;
+            }
+        }
+        self.inner.output(v);
+    }
+
+    fn try_input(&mut self) -> Option<i64> {
+        let v = self.inner.try_input()?;
+        self.command(v);
+        Some(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intcode::asm::assemble;
+    use intcode::io::QueueIo;
+    use intcode::{Intcode, StopReason};
+
+    // A droid in an east-west corridor, walled in to the north, south and
+    // west of where it starts, with the oxygen system two squares east.
+    const CORRIDOR: &str = "
+        loop:
+            in cmd
+            eq cmd, #4, t
+            jnz t, #east
+            eq cmd, #3, t
+            jnz t, #west
+            out #0
+            jnz #1, #loop
+        west:
+            lt #0, x, t
+            jz t, #wall
+            add x, #-1, x
+            out #1
+            jnz #1, #loop
+        east:
+            add x, #1, x
+            eq x, #2, t
+            jnz t, #oxygen
+            out #1
+            jnz #1, #loop
+        oxygen:
+            out #2
+            jnz #1, #loop
+        wall:
+            out #0
+            jnz #1, #loop
+    ";
+
+    fn explore(commands: &[MovementCommand]) -> MapRecorder<QueueIo> {
+        let mut io = QueueIo::new();
+        for &command in commands {
+            io.push_input(command.into());
+        }
+        let mut droid = Intcode::new(assemble(CORRIDOR).unwrap(), MapRecorder::new(io));
+        while droid.run_until_event().unwrap() != StopReason::NeedsInput {}
+        droid.into_io()
+    }
+
+    #[test]
+    fn records_walls_moves_and_oxygen() {
+        use MovementCommand::*;
+        let recorder = explore(&[North, West, East, East, West, South]);
+
+        assert_eq!(recorder.position(), (1, 0));
+        assert_eq!(recorder.oxygen(), Some((2, 0)));
+        let expected: HashMap<Point, StatusReply> = vec![
+            ((0, 0), StatusReply::Moved),
+            ((0, -1), StatusReply::HitWall),
+            ((-1, 0), StatusReply::HitWall),
+            ((1, 0), StatusReply::Moved),
+            ((2, 0), StatusReply::FoundOxygen),
+            ((1, 1), StatusReply::HitWall),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(recorder.map(), &expected);
+
+        // Everything still reaches the wrapped IO.
+        let mut io = recorder.into_inner();
+        let outputs: Vec<_> = std::iter::from_fn(|| io.pop_output()).collect();
+        assert_eq!(outputs, vec![0, 0, 1, 2, 1, 0]);
+    }
+
+    #[test]
+    fn walls_do_not_move_the_droid() {
+        use MovementCommand::*;
+        let recorder = explore(&[North, South, West]);
+        assert_eq!(recorder.position(), (0, 0));
+        assert_eq!(recorder.oxygen(), None);
+        assert_eq!(recorder.map().len(), 4);
+    }
+}
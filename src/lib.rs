@@ -1 +1,8 @@
-pub mod intcode;
+pub mod adventure;
+pub mod camera;
+pub mod donut;
+pub mod droid;
+pub mod network;
+pub mod protocol;
+pub mod routines;
+pub mod vault;
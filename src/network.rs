@@ -0,0 +1,243 @@
+//! Day 23's network: Intcode NICs, each booted with its address, sending each
+//! other `(destination, x, y)` packets, plus the NAT at address 255 that
+//! restarts the network by poking address 0 whenever everyone goes quiet.
+
+use intcode::cluster::MachineError;
+use intcode::io::QueueIo;
+use intcode::{Intcode, StopReason};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+pub const NAT_ADDRESS: i64 = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub destination: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// Something the NAT saw or did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NatEvent {
+    /// A NIC sent a packet to the NAT, replacing any it was holding.
+    Received(Packet),
+    /// The network was idle, so the NAT sent its packet on to address 0.
+    Sent(Packet),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkError {
+    Machine(MachineError),
+    /// A packet was sent to an address with no NIC behind it.
+    UnknownAddress(Packet),
+    /// The network went idle before the NAT had anything to send.
+    Stalled,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::Machine(error) => write!(f, "{}", error),
+            NetworkError::UnknownAddress(packet) => {
+                write!(f, "packet sent to unknown address {}", packet.destination)
+            }
+            NetworkError::Stalled => write!(f, "network idle with nothing for the NAT to send"),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+struct Nic {
+    machine: Intcode<QueueIo>,
+    // Outputs of a packet still being sent.
+    partial: Vec<i64>,
+    halted: bool,
+}
+
+/// NICs run in rounds: each takes a turn until it asks for input and, finding
+/// none waiting, is given `-1`. A round in which no NIC had input waiting or
+/// sent anything leaves the network idle.
+pub struct Network {
+    nics: Vec<Nic>,
+    nat: Option<Packet>,
+    events: VecDeque<NatEvent>,
+}
+
+impl Network {
+    /// Boots `size` NICs running `program`, with addresses `0..size`.
+    pub fn boot(program: Arc<[i64]>, size: usize) -> Network {
+        let nics = (0..size)
+            .map(|address| {
+                let mut io = QueueIo::new();
+                io.push_input(address as i64);
+                let mut machine = Intcode::shared(Arc::clone(&program), io);
+                machine.set_input_default(Some(-1));
+                Nic {
+                    machine,
+                    partial: Vec::with_capacity(3),
+                    halted: false,
+                }
+            })
+            .collect();
+        Network {
+            nics,
+            nat: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Runs the network until the NAT next receives or sends a packet.
+    pub fn next_event(&mut self) -> Result<NatEvent, NetworkError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            if !self.round()? {
+                let packet = self.nat.ok_or(NetworkError::Stalled)?;
+                let packet = Packet {
+                    destination: 0,
+                    ..packet
+                };
+                self.deliver(packet)?;
+                self.events.push_back(NatEvent::Sent(packet));
+            }
+        }
+    }
+
+    // Gives every NIC a turn, returning whether any of them did anything.
+    fn round(&mut self) -> Result<bool, NetworkError> {
+        let mut active = false;
+        let mut sent = Vec::new();
+        for address in 0..self.nics.len() {
+            let nic = &mut self.nics[address];
+            if nic.halted {
+                continue;
+            }
+            active |= nic.machine.io().has_input();
+            loop {
+                let stop = nic.machine.run_until_event().map_err(|error| {
+                    NetworkError::Machine(MachineError {
+                        machine: address,
+                        error,
+                    })
+                })?;
+                match stop {
+                    StopReason::Output(_) => {
+                        active = true;
+                        nic.partial.extend(nic.machine.io_mut().pop_output());
+                        if let [destination, x, y] = nic.partial[..] {
+                            sent.push(Packet { destination, x, y });
+                            nic.partial.clear();
+                        }
+                    }
+                    StopReason::DefaultInput => break,
+                    // The input default means the machine never waits.
+                    StopReason::NeedsInput => unreachable!(),
+                    StopReason::Halted => {
+                        nic.halted = true;
+                        break;
+                    }
+                    StopReason::Breakpoint(_) => (),
+                }
+            }
+            for packet in sent.drain(..) {
+                self.deliver(packet)?;
+            }
+        }
+        Ok(active)
+    }
+
+    fn deliver(&mut self, packet: Packet) -> Result<(), NetworkError> {
+        if packet.destination == NAT_ADDRESS {
+            self.nat = Some(packet);
+            self.events.push_back(NatEvent::Received(packet));
+            return Ok(());
+        }
+        let nic = usize::try_from(packet.destination)
+            .ok()
+            .and_then(|address| self.nics.get_mut(address))
+            .ok_or(NetworkError::UnknownAddress(packet))?;
+        let io = nic.machine.io_mut();
+        io.push_input(packet.x);
+        io.push_input(packet.y);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intcode::asm::assemble;
+
+    // Passes each packet on to the next address up with y incremented, or to
+    // the NAT from the last of three NICs. NIC 0 starts things off.
+    const RELAY: &str = "
+            in addr
+            jnz addr, #listen
+            out #1
+            out #7
+            out #0
+        listen:
+            in x
+            eq x, #-1, t
+            jnz t, #listen
+            in y
+            add y, #1, y
+            add addr, #1, dest
+            lt dest, #3, t
+            jnz t, #send
+            add #255, #0, dest
+        send:
+            out dest
+            out x
+            out y
+            jnz #1, #listen
+    ";
+
+    fn network(source: &str, size: usize) -> Network {
+        Network::boot(assemble(source).unwrap().into(), size)
+    }
+
+    #[test]
+    fn nat_restarts_idle_network() {
+        let mut network = network(RELAY, 3);
+        let packet = |destination, y| Packet {
+            destination,
+            x: 7,
+            y,
+        };
+        let events: Vec<NatEvent> = (0..4).map(|_| network.next_event().unwrap()).collect();
+        assert_eq!(
+            events,
+            [
+                NatEvent::Received(packet(NAT_ADDRESS, 2)),
+                NatEvent::Sent(packet(0, 2)),
+                NatEvent::Received(packet(NAT_ADDRESS, 5)),
+                NatEvent::Sent(packet(0, 5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn stalls_without_a_nat_packet() {
+        let mut network = network("loop: in x\njnz #1, #loop", 50);
+        assert_eq!(network.next_event(), Err(NetworkError::Stalled));
+    }
+
+    #[test]
+    fn unknown_address() {
+        let mut network = network("out #60\nout #1\nout #2\nhalt", 50);
+        assert_eq!(
+            network.next_event(),
+            Err(NetworkError::UnknownAddress(Packet {
+                destination: 60,
+                x: 1,
+                y: 2
+            }))
+        );
+    }
+}
@@ -0,0 +1,422 @@
+use aoc_utils::geom::Direction;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// A value read from (or meant for) an Intcode program that doesn't map onto
+/// any variant of the protocol type it was decoded as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownCode(pub i64);
+
+impl fmt::Display for UnknownCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown protocol code {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownCode {}
+
+// Day 11: hull painting robot.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    White,
+}
+
+impl From<Color> for i64 {
+    fn from(color: Color) -> Self {
+        match color {
+            Color::Black => 0,
+            Color::White => 1,
+        }
+    }
+}
+
+impl TryFrom<i64> for Color {
+    type Error = UnknownCode;
+
+    fn try_from(v: i64) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(Color::Black),
+            1 => Ok(Color::White),
+            _ => Err(UnknownCode(v)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    Left,
+    Right,
+}
+
+impl From<Turn> for i64 {
+    fn from(turn: Turn) -> Self {
+        match turn {
+            Turn::Left => 0,
+            Turn::Right => 1,
+        }
+    }
+}
+
+impl Turn {
+    /// Which way the robot faces after turning from `facing`.
+    pub fn apply(self, facing: Direction) -> Direction {
+        match self {
+            Turn::Left => facing.turn_left(),
+            Turn::Right => facing.turn_right(),
+        }
+    }
+}
+
+impl TryFrom<i64> for Turn {
+    type Error = UnknownCode;
+
+    fn try_from(v: i64) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(Turn::Left),
+            1 => Ok(Turn::Right),
+            _ => Err(UnknownCode(v)),
+        }
+    }
+}
+
+// Day 13: arcade cabinet.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+impl From<Tile> for i64 {
+    fn from(tile: Tile) -> Self {
+        match tile {
+            Tile::Empty => 0,
+            Tile::Wall => 1,
+            Tile::Block => 2,
+            Tile::Paddle => 3,
+            Tile::Ball => 4,
+        }
+    }
+}
+
+impl TryFrom<i64> for Tile {
+    type Error = UnknownCode;
+
+    fn try_from(v: i64) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(Tile::Empty),
+            1 => Ok(Tile::Wall),
+            2 => Ok(Tile::Block),
+            3 => Ok(Tile::Paddle),
+            4 => Ok(Tile::Ball),
+            _ => Err(UnknownCode(v)),
+        }
+    }
+}
+
+/// An `x, y, value` triple as several Intcode programs (day 13's arcade among
+/// them) output them. Positions with a negative coordinate aren't on screen;
+/// programs use them as sentinels to report something else entirely, so they
+/// are kept apart from ordinary cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Triple {
+    Cell { x: i64, y: i64, value: i64 },
+    Sentinel { x: i64, y: i64, value: i64 },
+}
+
+/// Assembles triples one output value at a time, as they arrive from the IO.
+#[derive(Debug, Clone, Default)]
+pub struct TripleDecoder {
+    pending: Vec<i64>,
+}
+
+impl TripleDecoder {
+    pub fn new() -> TripleDecoder {
+        TripleDecoder::default()
+    }
+
+    /// Returns a triple once `v` completes one.
+    pub fn push(&mut self, v: i64) -> Option<Triple> {
+        self.pending.push(v);
+        if self.pending.len() < 3 {
+            return None;
+        }
+        let (x, y, value) = (self.pending[0], self.pending[1], self.pending[2]);
+        self.pending.clear();
+        if x < 0 || y < 0 {
+            Some(Triple::Sentinel { x, y, value })
+        } else {
+            Some(Triple::Cell { x, y, value })
+        }
+    }
+}
+
+/// One complete triple of arcade output. The cabinet reports the score as the
+/// sentinel position `(-1, 0)`; any other sentinel is passed through as-is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArcadeEvent {
+    TileDrawn { x: i64, y: i64, tile: Tile },
+    Score(i64),
+    Sentinel { x: i64, y: i64, value: i64 },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ArcadeDecoder {
+    triples: TripleDecoder,
+}
+
+impl ArcadeDecoder {
+    pub fn new() -> ArcadeDecoder {
+        ArcadeDecoder::default()
+    }
+
+    /// Returns an event once `v` completes a triple.
+    pub fn push(&mut self, v: i64) -> Option<Result<ArcadeEvent, UnknownCode>> {
+        self.triples.push(v).map(|triple| match triple {
+            Triple::Sentinel { x: -1, y: 0, value } => Ok(ArcadeEvent::Score(value)),
+            Triple::Sentinel { x, y, value } => Ok(ArcadeEvent::Sentinel { x, y, value }),
+            Triple::Cell { x, y, value } => {
+                Tile::try_from(value).map(|tile| ArcadeEvent::TileDrawn { x, y, tile })
+            }
+        })
+    }
+
+    /// Decodes a whole captured output stream. A trailing partial triple is
+    /// ignored.
+    pub fn decode(outputs: &[i64]) -> Vec<Result<ArcadeEvent, UnknownCode>> {
+        let mut decoder = ArcadeDecoder::new();
+        outputs.iter().filter_map(|&v| decoder.push(v)).collect()
+    }
+}
+
+// Day 15: repair droid.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementCommand {
+    North,
+    South,
+    West,
+    East,
+}
+
+impl From<MovementCommand> for i64 {
+    fn from(command: MovementCommand) -> Self {
+        match command {
+            MovementCommand::North => 1,
+            MovementCommand::South => 2,
+            MovementCommand::West => 3,
+            MovementCommand::East => 4,
+        }
+    }
+}
+
+impl TryFrom<i64> for MovementCommand {
+    type Error = UnknownCode;
+
+    fn try_from(v: i64) -> Result<Self, Self::Error> {
+        match v {
+            1 => Ok(MovementCommand::North),
+            2 => Ok(MovementCommand::South),
+            3 => Ok(MovementCommand::West),
+            4 => Ok(MovementCommand::East),
+            _ => Err(UnknownCode(v)),
+        }
+    }
+}
+
+impl From<MovementCommand> for Direction {
+    fn from(command: MovementCommand) -> Self {
+        match command {
+            MovementCommand::North => Direction::North,
+            MovementCommand::South => Direction::South,
+            MovementCommand::West => Direction::West,
+            MovementCommand::East => Direction::East,
+        }
+    }
+}
+
+impl From<Direction> for MovementCommand {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::North => MovementCommand::North,
+            Direction::South => MovementCommand::South,
+            Direction::West => MovementCommand::West,
+            Direction::East => MovementCommand::East,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusReply {
+    HitWall,
+    Moved,
+    FoundOxygen,
+}
+
+impl From<StatusReply> for i64 {
+    fn from(reply: StatusReply) -> Self {
+        match reply {
+            StatusReply::HitWall => 0,
+            StatusReply::Moved => 1,
+            StatusReply::FoundOxygen => 2,
+        }
+    }
+}
+
+impl TryFrom<i64> for StatusReply {
+    type Error = UnknownCode;
+
+    fn try_from(v: i64) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(StatusReply::HitWall),
+            1 => Ok(StatusReply::Moved),
+            2 => Ok(StatusReply::FoundOxygen),
+            _ => Err(UnknownCode(v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+
+    fn round_trip<T>(values: &[T], codes: &[i64])
+    where
+        T: Copy + Debug + PartialEq + Into<i64> + TryFrom<i64, Error = UnknownCode>,
+    {
+        let encoded: Vec<i64> = values.iter().map(|&v| v.into()).collect();
+        assert_eq!(encoded, codes);
+        for (&v, &code) in values.iter().zip(codes) {
+            assert_eq!(T::try_from(code), Ok(v));
+        }
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        round_trip(&[Color::Black, Color::White], &[0, 1]);
+        round_trip(&[Turn::Left, Turn::Right], &[0, 1]);
+        round_trip(
+            &[
+                Tile::Empty,
+                Tile::Wall,
+                Tile::Block,
+                Tile::Paddle,
+                Tile::Ball,
+            ],
+            &[0, 1, 2, 3, 4],
+        );
+        round_trip(
+            &[
+                MovementCommand::North,
+                MovementCommand::South,
+                MovementCommand::West,
+                MovementCommand::East,
+            ],
+            &[1, 2, 3, 4],
+        );
+        round_trip(
+            &[
+                StatusReply::HitWall,
+                StatusReply::Moved,
+                StatusReply::FoundOxygen,
+            ],
+            &[0, 1, 2],
+        );
+    }
+
+    #[test]
+    fn unknown_codes() {
+        assert_eq!(Color::try_from(2), Err(UnknownCode(2)));
+        assert_eq!(Turn::try_from(-1), Err(UnknownCode(-1)));
+        assert_eq!(Tile::try_from(5), Err(UnknownCode(5)));
+        assert_eq!(MovementCommand::try_from(0), Err(UnknownCode(0)));
+        assert_eq!(StatusReply::try_from(3), Err(UnknownCode(3)));
+        assert_eq!(UnknownCode(7).to_string(), "unknown protocol code 7");
+    }
+
+    #[test]
+    fn score_interleaved_mid_frame() {
+        let outputs = [0, 0, 1, -1, 0, 12, 1, 0, 2, -1, 0, 13, 2, 0, 4];
+        assert_eq!(
+            ArcadeDecoder::decode(&outputs),
+            [
+                Ok(ArcadeEvent::TileDrawn {
+                    x: 0,
+                    y: 0,
+                    tile: Tile::Wall
+                }),
+                Ok(ArcadeEvent::Score(12)),
+                Ok(ArcadeEvent::TileDrawn {
+                    x: 1,
+                    y: 0,
+                    tile: Tile::Block
+                }),
+                Ok(ArcadeEvent::Score(13)),
+                Ok(ArcadeEvent::TileDrawn {
+                    x: 2,
+                    y: 0,
+                    tile: Tile::Ball
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn score_value_is_not_a_tile() {
+        // Small scores look like tile ids and large ones aren't tiles at all.
+        assert_eq!(
+            ArcadeDecoder::decode(&[-1, 0, 3, -1, 0, 99]),
+            [Ok(ArcadeEvent::Score(3)), Ok(ArcadeEvent::Score(99))]
+        );
+    }
+
+    #[test]
+    fn other_sentinels_stay_off_screen() {
+        assert_eq!(
+            ArcadeDecoder::decode(&[-1, 1, 2, 0, -1, 7]),
+            [
+                Ok(ArcadeEvent::Sentinel {
+                    x: -1,
+                    y: 1,
+                    value: 2
+                }),
+                Ok(ArcadeEvent::Sentinel {
+                    x: 0,
+                    y: -1,
+                    value: 7
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn direction_round_trips() {
+        for &direction in &Direction::ALL {
+            let code = i64::from(MovementCommand::from(direction));
+            let command = MovementCommand::try_from(code).unwrap();
+            assert_eq!(Direction::from(command), direction);
+            assert_eq!(Turn::Right.apply(Turn::Left.apply(direction)), direction);
+        }
+        assert_eq!(i64::from(MovementCommand::from(Direction::West)), 3);
+        assert_eq!(
+            Turn::try_from(1).unwrap().apply(Direction::North),
+            Direction::East
+        );
+        assert_eq!(MovementCommand::try_from(0), Err(UnknownCode(0)));
+    }
+
+    #[test]
+    fn partial_triple_waits_for_more() {
+        let mut decoder = ArcadeDecoder::new();
+        assert_eq!(decoder.push(-1), None);
+        assert_eq!(decoder.push(0), None);
+        assert_eq!(decoder.push(5), Some(Ok(ArcadeEvent::Score(5))));
+        assert_eq!(ArcadeDecoder::decode(&[3, 4, 2, 5, 6]).len(), 1);
+    }
+}
@@ -0,0 +1,299 @@
+//! Day 17's movement routines: a main routine of calls to functions A, B and
+//! C, each a list of turns and forward moves, all small enough for the
+//! vacuum robot's memory. A dry run replays them over the camera's view of
+//! the scaffold, so a bad compression shows up before the robot sets off.
+
+use crate::camera::{CameraView, Heading, Robot};
+use crate::protocol::Turn;
+use aoc_utils::geom::Point;
+use aoc_utils::grid::Grid;
+use std::fmt;
+
+/// The most characters the robot will take for any one routine, not counting
+/// the newline.
+pub const MAX_ROUTINE_LEN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Routine {
+    Main,
+    A,
+    B,
+    C,
+}
+
+impl fmt::Display for Routine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Routine::Main => write!(f, "main"),
+            Routine::A => write!(f, "A"),
+            Routine::B => write!(f, "B"),
+            Routine::C => write!(f, "C"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DryRunError {
+    /// A routine won't fit in the robot's memory.
+    TooLong(Routine),
+    /// Something in a routine that isn't a call (in main) or a turn or
+    /// number of steps (in a function).
+    BadToken(Routine, String),
+    /// The camera doesn't show an upright robot to start from.
+    NoRobot,
+    /// The robot stepped off the scaffold onto `position` during the `call`th
+    /// function call of the main routine, counting from 0.
+    Fell { call: usize, position: Point },
+    /// The routines finished without the robot visiting this scaffold cell,
+    /// the first such reading row by row.
+    Uncovered(Point),
+}
+
+impl fmt::Display for DryRunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DryRunError::TooLong(routine) => write!(f, "routine {} is too long", routine),
+            DryRunError::BadToken(routine, token) => {
+                write!(f, "routine {} has bad token {:?}", routine, token)
+            }
+            DryRunError::NoRobot => write!(f, "no upright robot in view"),
+            DryRunError::Fell { call, position } => write!(
+                f,
+                "robot fell off the scaffold at {:?} during call {}",
+                position, call
+            ),
+            DryRunError::Uncovered(p) => write!(f, "scaffold at {:?} never visited", p),
+        }
+    }
+}
+
+impl std::error::Error for DryRunError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Move {
+    Turn(Turn),
+    Forward(usize),
+}
+
+/// The text of the four routines, comma separated, as the robot reads them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Routines {
+    pub main: String,
+    pub a: String,
+    pub b: String,
+    pub c: String,
+}
+
+impl Routines {
+    pub fn new(main: &str, a: &str, b: &str, c: &str) -> Routines {
+        Routines {
+            main: main.to_string(),
+            a: a.to_string(),
+            b: b.to_string(),
+            c: c.to_string(),
+        }
+    }
+
+    fn text(&self, routine: Routine) -> &str {
+        match routine {
+            Routine::Main => &self.main,
+            Routine::A => &self.a,
+            Routine::B => &self.b,
+            Routine::C => &self.c,
+        }
+    }
+
+    /// What to send the robot: each routine on its own line, then whether to
+    /// show the continuous video feed.
+    pub fn input(&self, video: bool) -> Vec<i64> {
+        let feed = if video { "y" } else { "n" };
+        [&self.main, &self.a, &self.b, &self.c, feed]
+            .iter()
+            .flat_map(|line| line.bytes().chain(Some(b'\n')))
+            .map(i64::from)
+            .collect()
+    }
+
+    // Which function each call in main is to: 0 for A, 1 for B, 2 for C.
+    fn calls(&self) -> Result<Vec<usize>, DryRunError> {
+        self.main
+            .split(',')
+            .map(|token| match token {
+                "A" => Ok(0),
+                "B" => Ok(1),
+                "C" => Ok(2),
+                _ => Err(DryRunError::BadToken(Routine::Main, token.to_string())),
+            })
+            .collect()
+    }
+
+    fn moves(&self, function: Routine) -> Result<Vec<Move>, DryRunError> {
+        self.text(function)
+            .split(',')
+            .map(|token| match token {
+                "L" => Ok(Move::Turn(Turn::Left)),
+                "R" => Ok(Move::Turn(Turn::Right)),
+                _ => token
+                    .parse()
+                    .map(Move::Forward)
+                    .map_err(|_| DryRunError::BadToken(function, token.to_string())),
+            })
+            .collect()
+    }
+
+    /// Replays the routines from where the camera shows the robot, checking
+    /// that it stays on the scaffold and visits all of it. Returns where the
+    /// robot ends up.
+    pub fn dry_run(&self, view: &CameraView) -> Result<Robot, DryRunError> {
+        let routines = [Routine::Main, Routine::A, Routine::B, Routine::C];
+        if let Some(&routine) = routines
+            .iter()
+            .find(|&&routine| self.text(routine).len() > MAX_ROUTINE_LEN)
+        {
+            return Err(DryRunError::TooLong(routine));
+        }
+        let calls = self.calls()?;
+        let functions = [
+            self.moves(Routine::A)?,
+            self.moves(Routine::B)?,
+            self.moves(Routine::C)?,
+        ];
+
+        let (mut position, mut facing) = match view.robot {
+            Some(Robot {
+                position,
+                heading: Heading::Facing(facing),
+            }) => (position, facing),
+            _ => return Err(DryRunError::NoRobot),
+        };
+        let scaffold = &view.scaffold;
+        let mut visited = Grid::new(scaffold.width(), scaffold.height(), false);
+        visited[position] = true;
+        for (call, &function) in calls.iter().enumerate() {
+            for &step in &functions[function] {
+                match step {
+                    Move::Turn(turn) => facing = turn.apply(facing),
+                    Move::Forward(n) => {
+                        for _ in 0..n {
+                            position = facing.step(position);
+                            if scaffold.get(position) != Some(&true) {
+                                return Err(DryRunError::Fell { call, position });
+                            }
+                            visited[position] = true;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(p) = scaffold.points().find(|&p| scaffold[p] && !visited[p]) {
+            return Err(DryRunError::Uncovered(p));
+        }
+        Ok(Robot {
+            position,
+            heading: Heading::Facing(facing),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{Frame, FrameDecoder};
+    use aoc_utils::geom::Direction;
+
+    // The example from the puzzle's part 2.
+    const EXAMPLE: &str = "\
+#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......
+
+";
+
+    fn view() -> CameraView {
+        let ascii: Vec<i64> = EXAMPLE.bytes().map(i64::from).collect();
+        match FrameDecoder::decode(&ascii).as_slice() {
+            [Ok(Frame::Camera(view))] => view.clone(),
+            frames => panic!("unexpected frames {:?}", frames),
+        }
+    }
+
+    fn example(main: &str) -> Routines {
+        Routines::new(main, "R,8,R,8", "R,4,R,4,R,8", "L,6,L,2")
+    }
+
+    #[test]
+    fn example_covers_the_scaffold() {
+        assert_eq!(
+            example("A,B,C,B,A,C").dry_run(&view()),
+            Ok(Robot {
+                position: (0, 2),
+                heading: Heading::Facing(Direction::South),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_first_fall() {
+        assert_eq!(
+            example("A,B,C,B,B,C").dry_run(&view()),
+            Err(DryRunError::Fell {
+                call: 4,
+                position: (10, 7),
+            })
+        );
+        let mut routines = example("A,B,C,B,A,C");
+        routines.c = "L,6,L,3".to_string();
+        assert_eq!(
+            routines.dry_run(&view()),
+            Err(DryRunError::Fell {
+                call: 2,
+                position: (9, 4),
+            })
+        );
+    }
+
+    #[test]
+    fn reports_uncovered_scaffold() {
+        assert_eq!(
+            example("A,B,C").dry_run(&view()),
+            Err(DryRunError::Uncovered((0, 0)))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_routines() {
+        assert_eq!(
+            example("A,B,D").dry_run(&view()),
+            Err(DryRunError::BadToken(Routine::Main, "D".to_string()))
+        );
+        assert_eq!(
+            example("A,B,C,B,A,C,A,B,C,B,A").dry_run(&view()),
+            Err(DryRunError::TooLong(Routine::Main))
+        );
+        let mut routines = example("A");
+        routines.b = "R,4,F".to_string();
+        assert_eq!(
+            routines.dry_run(&view()),
+            Err(DryRunError::BadToken(Routine::B, "F".to_string()))
+        );
+    }
+
+    #[test]
+    fn input_is_one_line_per_routine() {
+        let input = example("A,B,C,B,A,C").input(false);
+        let text: String = input.iter().map(|&v| v as u8 as char).collect();
+        assert_eq!(text, "A,B,C,B,A,C\nR,8,R,8\nR,4,R,4,R,8\nL,6,L,2\nn\n");
+    }
+}
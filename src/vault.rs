@@ -0,0 +1,266 @@
+//! Day 18's vault maps: walls (`#`), open floor (`.`), entrances (`@`), keys
+//! (`a`-`z`) and the doors they open (`A`-`Z`).
+
+use aoc_utils::geom::Point;
+use aoc_utils::grid::Grid;
+use aoc_utils::search::multi_source_bfs;
+use std::collections::{BTreeMap, HashMap};
+
+fn is_open(c: char) -> bool {
+    c != '#'
+}
+
+fn is_door(c: char) -> bool {
+    c.is_ascii_uppercase()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimplificationStats {
+    pub open_before: usize,
+    pub open_after: usize,
+    pub doors_before: usize,
+    pub doors_after: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simplified {
+    pub map: Grid<char>,
+    pub stats: SimplificationStats,
+}
+
+/// Walls off every dead-end branch that leads to no key, along with any
+/// doors in them, since no search ever needs to go down one. What's left is
+/// ready for `search::contract` to collapse the corridors between keys.
+pub fn simplify(map: &Grid<char>) -> Simplified {
+    let count = |map: &Grid<char>, f: fn(char) -> bool| map.cells().filter(|&&c| f(c)).count();
+    let mut simplified = map.clone();
+    let mut candidates: Vec<Point> = map.points().collect();
+    while let Some(p) = candidates.pop() {
+        let c = simplified[p];
+        if !(c == '.' || is_door(c)) {
+            continue;
+        }
+        let open: Vec<Point> = simplified
+            .neighbors4(p)
+            .filter(|&q| is_open(simplified[q]))
+            .collect();
+        if open.len() <= 1 {
+            simplified[p] = '#';
+            candidates.extend(open);
+        }
+    }
+    Simplified {
+        stats: SimplificationStats {
+            open_before: count(map, is_open),
+            open_after: count(&simplified, is_open),
+            doors_before: count(map, is_door),
+            doors_after: count(&simplified, is_door),
+        },
+        map: simplified,
+    }
+}
+
+/// A set of keys, or of the doors they open, with bit 0 for `a`/`A`.
+pub type KeySet = u32;
+
+/// The bit for key or door `c`.
+pub fn key_bit(c: char) -> KeySet {
+    1 << (c.to_ascii_lowercase() as u32 - 'a' as u32)
+}
+
+/// What stands between the entrances and a key, whichever way you go.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Requirement {
+    pub doors: KeySet,
+    /// Keys that every route passes over, so they're always picked up first.
+    pub keys: KeySet,
+}
+
+/// For every key reachable from the entrances (ignoring doors), the doors
+/// and keys on every route to it: exactly those whose square, walled off,
+/// would cut the key off. A search can then skip keys whose doors it can't
+/// open yet.
+pub fn requirements(map: &Grid<char>) -> BTreeMap<char, Requirement> {
+    let reachable = |blocked: Option<Point>| {
+        let entrances = map.points().filter(|&p| map[p] == '@').map(|p| (p, 0));
+        multi_source_bfs(entrances, |&p, push| {
+            map.neighbors4(p)
+                .filter(|&q| is_open(map[q]) && Some(q) != blocked)
+                .for_each(push)
+        })
+        .distances
+    };
+    let everywhere = reachable(None);
+    let keys: HashMap<Point, char> = everywhere
+        .keys()
+        .filter(|&&p| map[p].is_ascii_lowercase())
+        .map(|&p| (p, map[p]))
+        .collect();
+    let mut result: BTreeMap<char, Requirement> = keys
+        .values()
+        .map(|&key| (key, Requirement::default()))
+        .collect();
+    for &blocker in everywhere.keys() {
+        let c = map[blocker];
+        if !c.is_ascii_alphabetic() {
+            continue;
+        }
+        let behind = reachable(Some(blocker));
+        for (&p, key) in &keys {
+            if p == blocker || behind.contains_key(&p) {
+                continue;
+            }
+            let requirement = result.get_mut(key).unwrap();
+            if is_door(c) {
+                requirement.doors |= key_bit(c);
+            } else {
+                requirement.keys |= key_bit(c);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(text: &str) -> Grid<char> {
+        Grid::parse(text.trim(), |c| c)
+    }
+
+    #[test]
+    fn fills_dead_ends() {
+        let vault = map("
+#########
+#.#.#..b#
+#.#.#C###
+#...@.#.#
+##.####.#
+#a..D...#
+#########
+");
+        let simplified = simplify(&vault);
+        assert_eq!(
+            simplified.map,
+            map("
+#########
+#####..b#
+#####C###
+##..@.###
+##.######
+#a.######
+#########
+")
+        );
+        assert_eq!(
+            simplified.stats,
+            SimplificationStats {
+                open_before: 23,
+                open_after: 11,
+                doors_before: 2,
+                doors_after: 1,
+            }
+        );
+    }
+
+    fn bits(keys: &str) -> KeySet {
+        keys.chars().map(key_bit).fold(0, |set, bit| set | bit)
+    }
+
+    fn requirement(doors: &str, keys: &str) -> Requirement {
+        Requirement {
+            doors: bits(doors),
+            keys: bits(keys),
+        }
+    }
+
+    #[test]
+    fn required_on_the_way() {
+        let vault = map("
+#########
+#b.A.@.a#
+#########
+");
+        assert_eq!(
+            requirements(&vault).into_iter().collect::<Vec<_>>(),
+            [('a', requirement("", "")), ('b', requirement("A", ""))]
+        );
+
+        let vault = map("
+########################
+#f.D.E.e.C.b.A.@.a.B.c.#
+######################.#
+#d.....................#
+########################
+");
+        assert_eq!(
+            requirements(&vault).into_iter().collect::<Vec<_>>(),
+            [
+                ('a', requirement("", "")),
+                ('b', requirement("A", "")),
+                ('c', requirement("B", "a")),
+                ('d', requirement("B", "ac")),
+                ('e', requirement("AC", "b")),
+                ('f', requirement("ACDE", "be")),
+            ]
+        );
+
+        let vault = map("
+########################
+#...............b.C.D.f#
+#.######################
+#.....@.a.B.c.d.A.e.F.g#
+########################
+");
+        assert_eq!(
+            requirements(&vault).into_iter().collect::<Vec<_>>(),
+            [
+                ('a', requirement("", "")),
+                ('b', requirement("", "")),
+                ('c', requirement("B", "a")),
+                ('d', requirement("B", "ac")),
+                ('e', requirement("AB", "acd")),
+                ('f', requirement("CD", "b")),
+                ('g', requirement("ABF", "acde")),
+            ]
+        );
+    }
+
+    #[test]
+    fn loops_and_entrances() {
+        // Either way round the loop works, so neither door is required; and
+        // each entrance has its own way in.
+        let vault = map("
+#########
+#.A.b.B.#
+#.#####.#
+#...@...#
+#########
+#@.C.c###
+#########
+");
+        assert_eq!(
+            requirements(&vault).into_iter().collect::<Vec<_>>(),
+            [('b', requirement("", "")), ('c', requirement("C", ""))]
+        );
+    }
+
+    #[test]
+    fn examples_have_no_dead_ends() {
+        let vault = map("
+#################
+#i.G..c...e..H.p#
+########.########
+#j.A..b...f..D.o#
+########@########
+#k.E..a...g..B.n#
+########.########
+#l.F..d...h..C.m#
+#################
+");
+        let simplified = simplify(&vault);
+        assert_eq!(simplified.map, vault);
+        assert_eq!(simplified.stats.open_before, simplified.stats.open_after);
+    }
+}